@@ -1,21 +1,69 @@
-use clap::{Parser, ValueEnum};
+use crate::fsutil::StagingStrategy;
+use crate::output::OutputFormat;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use std::ffi::OsString;
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(name = "boiler")]
 pub struct Args {
-    /// Path to .boiler.yini configuration
-    #[arg(value_name = "INI")]
-    pub(crate) ini: PathBuf,
+    #[command(subcommand)]
+    pub(crate) command: Command,
 
-    /// Optional GitHub token for private assets (env GITHUB_TOKEN also supported)
-    #[arg(long)]
-    pub(crate) github_token: Option<String>,
+    /// Progress output: human (default) or json (one event per line)
+    #[arg(long, global = true, value_enum, default_value = "human")]
+    pub(crate) output: OutputFormat,
 
-    /// steam sdk
-    #[arg(long, default_value = "steam_redist")]
-    pub(crate) steam_redist: PathBuf,
+    /// Print more detail, e.g. every file left out by the content excludes
+    #[arg(long, short, global = true)]
+    pub(crate) verbose: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Clean, fetch and stage everything, then write the .vdf files (the default)
+    Build(BuildArgs),
+    /// Clean, fetch and stage content and binaries into the build directory
+    Stage(StageArgs),
+    /// Regenerate the .vdf files for an already staged build directory
+    Vdf(VdfArgs),
+    /// Upload a previously built app_build VDF with steamcmd
+    Upload(UploadArgs),
+    /// Delete the build and temp directories
+    Clean(CleanArgs),
+    /// Check that the configuration is complete and consistent
+    Validate(ConfigArgs),
+    /// Show what the configuration resolves to
+    Info(ConfigArgs),
+    /// Work with the per-depot file manifests written by stage
+    #[command(subcommand)]
+    Manifest(ManifestCommand),
+}
 
+#[derive(Subcommand, Debug)]
+pub enum ManifestCommand {
+    /// Show added, removed and changed files between two builds
+    Diff(ManifestDiffArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ManifestDiffArgs {
+    /// Old manifest file, manifests dir or build dir
+    pub(crate) old: PathBuf,
+
+    /// New manifest file, manifests dir or build dir
+    pub(crate) new: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ConfigArgs {
+    /// Path to .boiler.yini configuration
+    #[arg(value_name = "INI", required = true)]
+    pub(crate) ini: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DirArgs {
     /// Staging root (default: ./build)
     #[arg(long, default_value = "build")]
     pub(crate) build_dir: PathBuf,
@@ -24,10 +72,48 @@ pub struct Args {
     #[arg(long, default_value = "temp")]
     pub(crate) temp_dir: PathBuf,
 
+    /// Delete --build-dir and --temp-dir even if boiler did not create them
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct BranchArgs {
     /// Optional Steam setlive branch to set (omit to not set live)
     #[arg(long = "live-branch", aliases = ["setlive"], value_name = "STEAM_BRANCH")]
     pub(crate) live_branch: Option<String>,
 
+    /// Do not ask for confirmation when setting a protected branch live
+    #[arg(long)]
+    pub(crate) yes: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct StageArgs {
+    #[command(flatten)]
+    pub(crate) config: ConfigArgs,
+
+    #[command(flatten)]
+    pub(crate) dirs: DirArgs,
+
+    /// Optional GitHub token for private assets and workflow artifacts. Shows up in the process
+    /// list, prefer --token-file, GITHUB_TOKEN or `gh auth login`
+    #[arg(long)]
+    pub(crate) github_token: Option<String>,
+
+    /// Read the token for the binaries provider (and a content repo on the same host) from this
+    /// file. Otherwise GITHUB_TOKEN, GITLAB_TOKEN or GITEA_TOKEN, then `gh auth token` are tried
+    #[arg(long)]
+    pub(crate) token_file: Option<PathBuf>,
+
+    /// When no token is found, use the password git's credential helper has stored for the host
+    #[arg(long)]
+    pub(crate) token_from_git_credential: bool,
+
+    /// steam sdk
+    #[arg(long, default_value = "steam_redist")]
+    pub(crate) steam_redist: PathBuf,
+
     /// Leave build directory intact (do not delete at start)
     #[arg(long)]
     pub(crate) keep_build_dir: bool,
@@ -36,10 +122,133 @@ pub struct Args {
     /// Example: --targets mac,content
     #[arg(long, value_delimiter = ',', value_enum)]
     pub(crate) targets: Vec<Target>,
+
+    /// Print what would be deleted, downloaded, copied and generated, without doing it
+    #[arg(long)]
+    pub(crate) dry_run: bool,
+
+    /// How files are put into the build dir: copy, hardlink, reflink or auto
+    #[arg(long, value_enum, default_value = "copy")]
+    pub(crate) staging: StagingStrategy,
+
+    /// Keep a mirror of the content repo here and only fetch new objects into it
+    /// (default: clone from scratch every time)
+    #[arg(long)]
+    pub(crate) cache_dir: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct BuildArgs {
+    #[command(flatten)]
+    pub(crate) stage: StageArgs,
+
+    #[command(flatten)]
+    pub(crate) branch: BranchArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct VdfArgs {
+    #[command(flatten)]
+    pub(crate) config: ConfigArgs,
+
+    /// Staging root (default: ./build)
+    #[arg(long, default_value = "build")]
+    pub(crate) build_dir: PathBuf,
+
+    #[command(flatten)]
+    pub(crate) branch: BranchArgs,
+
+    /// Which depots to write. Comma-separated: linux, mac, windows, content
+    /// (default: every depot that has a staged directory)
+    #[arg(long, value_delimiter = ',', value_enum)]
+    pub(crate) targets: Vec<Target>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct UploadArgs {
+    #[command(flatten)]
+    pub(crate) config: ConfigArgs,
+
+    /// Staging root holding the generated .vdf files (default: ./build)
+    #[arg(long, default_value = "build")]
+    pub(crate) build_dir: PathBuf,
+
+    /// steamcmd executable to run (default: steamcmd on PATH)
+    #[arg(long, default_value = "steamcmd")]
+    pub(crate) steamcmd: PathBuf,
+
+    /// Steam account name passed to `+login` (env STEAM_LOGIN also supported)
+    #[arg(long, value_name = "STEAM_NAME")]
+    pub(crate) steam_login: Option<String>,
+
+    /// Do not ask for confirmation when setting a protected branch live
+    #[arg(long)]
+    pub(crate) yes: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CleanArgs {
+    /// Optional .boiler.yini configuration, its directory is never deleted
+    #[arg(value_name = "INI")]
+    pub(crate) ini: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub(crate) dirs: DirArgs,
+
+    /// Print what would be deleted, without doing it
+    #[arg(long)]
+    pub(crate) dry_run: bool,
 }
 
 pub fn parse() -> Args {
-    Args::parse()
+    Args::parse_from(default_to_build(std::env::args_os().collect()))
+}
+
+/// `boiler your.yini ...` is the same as `boiler build your.yini ...`, also after global
+/// options (`boiler -v your.yini`).
+fn default_to_build(mut argv: Vec<OsString>) -> Vec<OsString> {
+    let command = Args::command();
+    let mut index = 1;
+    while let Some(arg) = argv.get(index).and_then(|arg| arg.to_str()) {
+        let global = if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, _)) => (name, true),
+                None => (long, false),
+            };
+            command
+                .get_arguments()
+                .find(|option| option.get_long() == Some(name))
+                .map(|option| option.get_action().takes_values() && !value)
+        } else if let Some(short) = arg.strip_prefix('-')
+            && let [short] = short.chars().collect::<Vec<_>>()[..]
+        {
+            command
+                .get_arguments()
+                .find(|option| option.get_short() == Some(short))
+                .map(|option| option.get_action().takes_values())
+        } else {
+            None
+        };
+        match global {
+            // and its value
+            Some(true) => index += 2,
+            Some(false) => index += 1,
+            None => break,
+        }
+    }
+
+    let Some(first) = argv.get(index).and_then(|arg| arg.to_str()) else {
+        return argv;
+    };
+    let is_help = matches!(first, "-h" | "--help");
+    let is_subcommand = first == "help"
+        || command
+            .get_subcommands()
+            .any(|subcommand| subcommand.get_name() == first);
+    if !is_help && !is_subcommand {
+        argv.insert(index, OsString::from("build"));
+    }
+    argv
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, ValueEnum)]
@@ -55,3 +264,61 @@ pub enum Target {
     /// Game content (data/) and depot
     Content,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        let argv = line.split(' ').map(OsString::from).collect();
+        default_to_build(argv)
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn yini_defaults_to_build() {
+        assert_eq!(args("boiler x.yini"), ["boiler", "build", "x.yini"]);
+        assert_eq!(
+            args("boiler x.yini --dry-run"),
+            ["boiler", "build", "x.yini", "--dry-run"]
+        );
+    }
+
+    #[test]
+    fn subcommands_and_help_are_kept() {
+        assert_eq!(args("boiler info x.yini"), ["boiler", "info", "x.yini"]);
+        assert_eq!(args("boiler build x.yini"), ["boiler", "build", "x.yini"]);
+        assert_eq!(args("boiler help"), ["boiler", "help"]);
+        assert_eq!(args("boiler --help"), ["boiler", "--help"]);
+        assert_eq!(args("boiler"), ["boiler"]);
+    }
+
+    #[test]
+    fn global_options_before_a_subcommand() {
+        assert_eq!(
+            args("boiler --output json info x.yini"),
+            ["boiler", "--output", "json", "info", "x.yini"]
+        );
+        assert_eq!(
+            args("boiler -v validate x.yini"),
+            ["boiler", "-v", "validate", "x.yini"]
+        );
+        assert_eq!(
+            args("boiler --verbose --output=json build x.yini"),
+            ["boiler", "--verbose", "--output=json", "build", "x.yini"]
+        );
+    }
+
+    #[test]
+    fn global_options_before_a_yini() {
+        assert_eq!(
+            args("boiler --output json -v x.yini"),
+            ["boiler", "--output", "json", "-v", "build", "x.yini"]
+        );
+        let parsed = Args::try_parse_from(args("boiler --output json -v x.yini")).unwrap();
+        assert!(parsed.verbose);
+        assert!(matches!(parsed.command, Command::Build(_)));
+    }
+}
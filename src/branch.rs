@@ -0,0 +1,67 @@
+use crate::yini::BoilerYini;
+use anyhow::{Context, anyhow};
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Check that `branch` may be set live: never `default`/`public`, listed in
+/// `allowed_branches` (when configured) and, for `protected_branches`, confirmed by the user.
+pub fn check_live_branch(branch: &str, ini: &BoilerYini, assume_yes: bool) -> anyhow::Result<()> {
+    let branch_lower = branch.to_lowercase();
+
+    // Refuse unsafe/public branches
+    if branch_lower == "default" || branch_lower == "public" {
+        return Err(anyhow!(
+            "refusing to use forbidden Steam branch '{}' (use a non-public branch)",
+            branch
+        ));
+    }
+
+    if !ini.allowed_branches.is_empty()
+        && !ini
+            .allowed_branches
+            .iter()
+            .any(|allowed| allowed.to_lowercase() == branch_lower)
+    {
+        return Err(anyhow!(
+            "Steam branch '{}' is not in allowed_branches ({})",
+            branch,
+            ini.allowed_branches.join(", ")
+        ));
+    }
+
+    let is_protected = ini
+        .protected_branches
+        .iter()
+        .any(|protected| protected.to_lowercase() == branch_lower);
+    if is_protected && !assume_yes {
+        confirm_protected_branch(branch)?;
+    }
+
+    Ok(())
+}
+
+fn confirm_protected_branch(branch: &str) -> anyhow::Result<()> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        return Err(anyhow!(
+            "Steam branch '{}' is protected, pass --yes to confirm",
+            branch
+        ));
+    }
+
+    eprint!("⚠️ '{branch}' is a protected Steam branch, type its name to continue: ");
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    stdin
+        .lock()
+        .read_line(&mut answer)
+        .context("reading confirmation")?;
+    if answer.trim() != branch {
+        return Err(anyhow!(
+            "not confirmed, leaving Steam branch '{}' alone",
+            branch
+        ));
+    }
+
+    Ok(())
+}
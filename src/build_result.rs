@@ -0,0 +1,201 @@
+use crate::{DepotId, SteamAppId};
+use anyhow::Context;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// What Steam made of an upload, written to `build_result.json` next to the VDFs.
+#[derive(Debug, Serialize)]
+pub struct BuildResult {
+    pub app_id: SteamAppId,
+    pub build_id: u64,
+    /// depot id -> manifest id. Manifest ids use the full 64 bits, so they are written as
+    /// strings to survive JSON readers that only have doubles.
+    pub depots: BTreeMap<DepotId, String>,
+    pub live_branch: Option<String>,
+    pub uploaded_at_utc: String,
+}
+
+pub fn write_build_result(path: &Path, result: &BuildResult) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(result)?;
+    fs::write(path, json).with_context(|| format!("writing {}", path.display()))?;
+    Ok(())
+}
+
+/// Collect the manifest id for each of `depots` from what steamcmd left behind.
+///
+/// Looks at the steamcmd console `log` first, then at the `*.log` files and the
+/// `<depot>_<manifest>.manifest` files in `build_output`. Files older than `since`
+/// are from earlier uploads and are ignored.
+pub fn find_depot_manifests(
+    log: &[String],
+    build_output: &Path,
+    since: SystemTime,
+    depots: &[DepotId],
+) -> BTreeMap<DepotId, u64> {
+    let mut found = BTreeMap::new();
+
+    for line in log {
+        if let Some((depot, manifest)) = parse_manifest_line(line, None) {
+            found.entry(depot).or_insert(manifest);
+        }
+    }
+
+    for entry in WalkDir::new(build_output).into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let is_recent = entry
+            .metadata()
+            .ok()
+            .and_then(|m| m.modified().ok())
+            .is_some_and(|modified| modified >= since);
+        if !is_recent {
+            continue;
+        }
+
+        let file_name = entry.file_name().to_string_lossy();
+        if let Some(stem) = file_name.strip_suffix(".manifest") {
+            if let Some((depot, manifest)) = stem.split_once('_')
+                && let (Ok(depot), Ok(manifest)) = (depot.parse(), manifest.parse())
+            {
+                found.entry(depot).or_insert(manifest);
+            }
+        } else if let Some(stem) = file_name.strip_suffix(".log") {
+            // depot_build_1234561.log only talks about depot 1234561
+            let file_depot = stem
+                .strip_prefix("depot_build_")
+                .and_then(|id| id.parse().ok());
+            let Ok(contents) = fs::read(entry.path()) else {
+                continue;
+            };
+            for line in String::from_utf8_lossy(&contents).lines() {
+                if let Some((depot, manifest)) = parse_manifest_line(line, file_depot) {
+                    found.entry(depot).or_insert(manifest);
+                }
+            }
+        }
+    }
+
+    found.retain(|depot, _| depots.contains(depot));
+    found
+}
+
+/// Finds `depot <id> ... manifest <id>` in a log line. `default_depot` is used when the
+/// line only mentions the manifest.
+fn parse_manifest_line(line: &str, default_depot: Option<DepotId>) -> Option<(DepotId, u64)> {
+    let lower = line.to_ascii_lowercase();
+    let manifest = number_after(&lower, "manifest")?;
+    let depot = number_after(&lower, "depot").or(default_depot)?;
+    Some((depot, manifest))
+}
+
+/// The first number shortly after `word`, skipping separators such as `:`, ` id ` or `build`.
+fn number_after(line: &str, word: &str) -> Option<u64> {
+    const MAX_GAP: usize = 12;
+    const FILLER_WORDS: [&str; 3] = ["id", "gid", "build"];
+    let mut rest = line;
+    while let Some(index) = rest.find(word) {
+        rest = &rest[index + word.len()..];
+        let digits_start = rest.find(|c: char| c.is_ascii_digit())?;
+        let only_filler = rest[..digits_start]
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .all(|gap_word| gap_word.is_empty() || FILLER_WORDS.contains(&gap_word));
+        if digits_start > MAX_GAP || !only_filler {
+            continue;
+        }
+        let digits: String = rest[digits_start..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        if let Ok(number) = digits.parse() {
+            return Some(number);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depot_and_manifest_on_one_line() {
+        for line in [
+            "Depot 1234561 : Successfully committed manifest 7755443322110099 (12 files)",
+            "[2025-03-01 10:21:07]: DepotBuild 1234564 - manifest 8134906237492012384",
+            "Depot 1234561 manifest GID 8134906237492012384",
+            "Successfully finished depot build (depot: 1234561, manifest id: 8134906237492012384)",
+        ] {
+            assert!(parse_manifest_line(line, None).is_some(), "{line}");
+        }
+        assert_eq!(
+            parse_manifest_line("DepotBuild 1234564 - manifest 8134906237492012384", None),
+            Some((1234564, 8134906237492012384))
+        );
+    }
+
+    #[test]
+    fn manifest_only_lines_use_the_log_depot() {
+        let line = "[2025-03-01 10:21:07]: Created manifest 8134906237492012384 (1.2 GB)";
+        assert_eq!(parse_manifest_line(line, None), None);
+        assert_eq!(
+            parse_manifest_line(line, Some(1234561)),
+            Some((1234561, 8134906237492012384))
+        );
+    }
+
+    #[test]
+    fn lines_that_must_not_match() {
+        for line in [
+            "Successfully finished AppID 1234560 build (BuildID 12345678).",
+            "[2025-03-01 10:21:03]: Building depot 1234561...",
+            "[2025-03-01 10:21:04]: Building file mapping...",
+            "Uploading content...",
+            // the number is too far from `manifest`
+            "Depot 1234561: writing manifest file to C:\\builds\\output\\1234561_42.manifest",
+            "Loading depot manifest for depot 1234561",
+        ] {
+            assert_eq!(parse_manifest_line(line, None), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn number_after_skips_separators() {
+        assert_eq!(number_after("manifest: 42", "manifest"), Some(42));
+        assert_eq!(number_after("manifest id 42", "manifest"), Some(42));
+        assert_eq!(number_after("manifest (gid 42)", "manifest"), Some(42));
+        assert_eq!(number_after("manifest is not ready, 42", "manifest"), None);
+        // the second `depot` is close to its number
+        assert_eq!(
+            number_after("depot files are queued, depot 7", "depot"),
+            Some(7)
+        );
+    }
+
+    #[test]
+    fn finds_manifests_in_build_output() {
+        let output = tempfile::tempdir().unwrap();
+        let since = SystemTime::now() - std::time::Duration::from_secs(60);
+        fs::write(
+            output.path().join("depot_build_1234561.log"),
+            "[2025-03-01 10:21:07]: Created manifest 111 (1.2 GB)\n",
+        )
+        .unwrap();
+        fs::write(output.path().join("1234562_222.manifest"), "").unwrap();
+        let log = vec![
+            "Depot 1234563 : Successfully committed manifest 333 (4 files)".to_string(),
+            "Depot 9999999 : Successfully committed manifest 999 (4 files)".to_string(),
+        ];
+
+        let found = find_depot_manifests(&log, output.path(), since, &[1234561, 1234562, 1234563]);
+
+        assert_eq!(
+            found,
+            BTreeMap::from([(1234561, 111), (1234562, 222), (1234563, 333)])
+        );
+    }
+}
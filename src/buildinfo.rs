@@ -0,0 +1,153 @@
+use crate::output::{Event, emit, redact};
+use crate::{DepotId, SteamAppId};
+use anyhow::{Context, anyhow};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+pub const CONTENT_TXT: &str = "buildinfo_content.txt";
+pub const BINARIES_TXT: &str = "buildinfo_binaries.txt";
+pub const JSON: &str = "buildinfo.json";
+
+/// Which build info files to write into each depot, `buildinfo_format` in the yini.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum BuildInfoFormat {
+    #[default]
+    Txt,
+    Json,
+    Both,
+}
+
+impl BuildInfoFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            BuildInfoFormat::Txt => "txt",
+            BuildInfoFormat::Json => "json",
+            BuildInfoFormat::Both => "both",
+        }
+    }
+
+    pub fn writes_txt(self) -> bool {
+        self != BuildInfoFormat::Json
+    }
+
+    pub fn writes_json(self) -> bool {
+        self != BuildInfoFormat::Txt
+    }
+
+    /// File names written for a depot whose text file is `txt_name`.
+    pub fn file_names(self, txt_name: &'static str) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if self.writes_txt() {
+            names.push(txt_name);
+        }
+        if self.writes_json() {
+            names.push(JSON);
+        }
+        names
+    }
+}
+
+impl FromStr for BuildInfoFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "txt" => Ok(BuildInfoFormat::Txt),
+            "json" => Ok(BuildInfoFormat::Json),
+            "both" => Ok(BuildInfoFormat::Both),
+            _ => Err(anyhow!("expected `txt`, `json` or `both`, got '{s}'")),
+        }
+    }
+}
+
+/// Where the files of one depot came from. `buildinfo.json` is this struct as is, the text
+/// file only has the fields it always had.
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    /// The content or binaries repo: `org/name` (`group/subgroup/name` on GitLab), a git URL,
+    /// a local path, or the URL the binaries were downloaded from.
+    pub repo: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub committed_at_utc: Option<String>,
+    /// Submodule path -> checked out commit.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub submodules: BTreeMap<String, String>,
+    /// A local content directory had uncommitted changes.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub modified: bool,
+    /// Workflow run the binaries artifact came from, `commit` is what it built.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Branch (`refs/heads/main`) or release tag (`v0.1.2`) that was resolved, empty for a
+    /// `url` or `path`.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub resolved_ref: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_sha256: Option<String>,
+    pub built_at_utc: String,
+    pub boiler_version: &'static str,
+    pub steam_app_id: SteamAppId,
+    pub steam_depot_id: DepotId,
+}
+
+impl BuildInfo {
+    fn to_txt(&self) -> String {
+        let mut txt = format!("repo: {}\n", self.repo);
+        if let Some(commit) = &self.commit {
+            txt += &format!("commit: {commit}\n");
+        }
+        if let Some(committed_at_utc) = &self.committed_at_utc {
+            txt += &format!("committed_at_utc: {committed_at_utc}\n");
+        }
+        for (path, commit) in &self.submodules {
+            txt += &format!("submodule {path}: {commit}\n");
+        }
+        if self.modified {
+            txt += "modified: true\n";
+        }
+        if let Some(run_id) = self.run_id {
+            txt += &format!("run_id: {run_id}\n");
+        }
+        if let Some(version) = &self.version {
+            txt += &format!("version: {version}\n");
+        }
+        txt += &format!("built_at_utc: {}\n", self.built_at_utc);
+        txt
+    }
+}
+
+/// Write the build info files into `dir` (a staging dir). `reported_dir` is where they end up
+/// in the build dir, used for the events. They ship to players, so secrets are redacted.
+pub fn write_buildinfo(
+    dir: &Path,
+    reported_dir: &Path,
+    txt_name: &'static str,
+    info: &BuildInfo,
+    format: BuildInfoFormat,
+) -> anyhow::Result<()> {
+    if format.writes_txt() {
+        let path = dir.join(txt_name);
+        fs::write(&path, redact(&info.to_txt()))
+            .with_context(|| format!("writing {}", path.display()))?;
+    }
+    if format.writes_json() {
+        let path = dir.join(JSON);
+        fs::write(&path, redact(&serde_json::to_string_pretty(info)?) + "\n")
+            .with_context(|| format!("writing {}", path.display()))?;
+    }
+    for name in format.file_names(txt_name) {
+        emit(Event::BuildinfoWritten {
+            path: reported_dir.join(name),
+        });
+    }
+    Ok(())
+}
@@ -1,9 +1,51 @@
+use crate::output::{Event, emit, register_secret};
 use anyhow::{Context, anyhow};
 use reqwest::blocking::Client;
-use reqwest::header::CONTENT_DISPOSITION;
+use reqwest::header::{CONTENT_DISPOSITION, CONTENT_LENGTH, ETAG};
 use std::path::{Path, PathBuf};
-use std::{fs, io};
+use std::{env, fs, io};
 
+/// A request header whose value is read from an environment variable, so secrets stay out of
+/// the yini.
+#[derive(Debug, Clone)]
+pub struct EnvHeader {
+    pub name: String,
+    pub env: String,
+}
+
+/// Header names and values, failing on the first variable that is not set. The values are
+/// registered as secrets.
+pub fn resolve_headers(headers: &[EnvHeader]) -> anyhow::Result<Vec<(String, String)>> {
+    headers
+        .iter()
+        .map(|header| {
+            env::var(&header.env)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| {
+                    register_secret(&value);
+                    (header.name.clone(), value)
+                })
+                .ok_or_else(|| {
+                    anyhow!(
+                        "header {}: environment variable {} is not set",
+                        header.name,
+                        header.env
+                    )
+                })
+        })
+        .collect()
+}
+
+/// HTTP client for every request, APIs like GitHub's reject requests without a user agent.
+pub fn http_client() -> Client {
+    Client::builder()
+        .user_agent(concat!("boiler/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("building the HTTP client")
+}
+
+#[allow(clippy::collapsible_if, clippy::double_ended_iterator_last)]
 fn filename_from_headers_or_url(resp: &reqwest::blocking::Response, url: &str) -> PathBuf {
     if let Some(cd) = resp.headers().get(CONTENT_DISPOSITION) {
         if let Ok(s) = cd.to_str() {
@@ -25,25 +67,67 @@ fn filename_from_headers_or_url(resp: &reqwest::blocking::Response, url: &str) -
     PathBuf::from("download.bin")
 }
 
+/// Download `url` into `tmp_dir`, as `file_name` or the name the server or URL gives it.
 pub fn download_to_with_name(
     tmp_dir: &Path,
     url: &str,
-    token: Option<&str>,
+    headers: &[(String, String)],
+    file_name: Option<&str>,
 ) -> anyhow::Result<PathBuf> {
-    let client = Client::new();
+    let client = http_client();
     let mut req = client.get(url);
-    if let Some(t) = token {
-        req = req
-            .bearer_auth(t)
-            .header("Accept", "application/octet-stream");
+    for (name, value) in headers {
+        req = req.header(name, value);
     }
     let mut resp = req.send().context("downloading asset")?;
     if !resp.status().is_success() {
         return Err(anyhow!("download failed: {} {}", resp.status(), url));
     }
-    let fname = filename_from_headers_or_url(&resp, url);
+    emit(Event::AssetResolved {
+        url: url.to_string(),
+        size: resp.content_length(),
+    });
+    let fname = match file_name {
+        Some(name) => PathBuf::from(name),
+        None => filename_from_headers_or_url(&resp, url),
+    };
     let out_path = tmp_dir.join(fname);
     let mut out = fs::File::create(&out_path)?;
-    io::copy(&mut resp, &mut out)?;
+    let bytes = io::copy(&mut resp, &mut out)?;
+    emit(Event::Downloaded {
+        url: url.to_string(),
+        bytes,
+    });
     Ok(out_path)
 }
+
+/// What a HEAD request told us about a download.
+#[derive(Debug)]
+pub struct RemoteAsset {
+    pub size: Option<u64>,
+    pub etag: Option<String>,
+}
+
+/// HEAD request to check that `url` can be downloaded.
+pub fn probe(url: &str, headers: &[(String, String)]) -> anyhow::Result<RemoteAsset> {
+    let client = http_client();
+    let mut req = client.head(url);
+    for (name, value) in headers {
+        req = req.header(name, value);
+    }
+    let resp = req.send().context("probing asset")?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("not downloadable: {} {}", resp.status(), url));
+    }
+    // content_length() reports the (empty) HEAD body, so read the header instead
+    let header = |name| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    Ok(RemoteAsset {
+        size: header(CONTENT_LENGTH).and_then(|v| v.parse().ok()),
+        etag: header(ETAG),
+    })
+}
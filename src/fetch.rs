@@ -1,18 +1,102 @@
 use crate::download::download_to_with_name;
 use crate::extract::extract_archive;
-use std::fs;
+use crate::fsutil::{CollisionPolicy, copy_dir_recursive};
+use crate::output::progress;
+use sha2::{Digest, Sha256};
 use std::path::Path;
+use std::{fs, io};
 use tempfile::tempdir;
 
-/// Download URL to temp file (supports optional GitHub token), then extract to `dest_root`.
-pub fn extract_to_target(url: &str, token: Option<&str>, dest_root: &Path) -> anyhow::Result<()> {
-    println!("📡fetching {}", url);
+/// The archive that was extracted, or the directory that was copied.
+#[derive(Debug, Clone)]
+pub struct FetchedAsset {
+    pub name: String,
+    /// `None` for a directory.
+    pub sha256: Option<String>,
+}
+
+/// Download URL to temp file (with the auth or other request headers), then extract to
+/// `dest_root`. `file_name` overrides the name of the download, which picks the archive type.
+pub fn extract_to_target(
+    url: &str,
+    headers: &[(String, String)],
+    file_name: Option<&str>,
+    dest_root: &Path,
+) -> anyhow::Result<FetchedAsset> {
+    progress!("📡fetching {}", url);
+    let dir = tempdir()?;
+    let archive_path = download_to_with_name(dir.path(), url, headers, file_name)?;
+    extract_local_archive(&archive_path, dest_root)
+}
+
+/// Workflow artifacts are always zipped, so binaries that need their file permissions are
+/// uploaded as a tarball inside the zip. Extract such a lone archive in `dest_root` too.
+pub fn extract_nested_archive(dest_root: &Path) -> anyhow::Result<()> {
+    let entries = fs::read_dir(dest_root)?.collect::<io::Result<Vec<_>>>()?;
+    let [entry] = entries.as_slice() else {
+        return Ok(());
+    };
+    let name = entry.file_name().to_string_lossy().to_ascii_lowercase();
+    let is_archive = [".zip", ".tar.gz", ".tgz", ".tar"]
+        .iter()
+        .any(|extension| name.ends_with(extension));
+    if !entry.file_type()?.is_file() || !is_archive {
+        return Ok(());
+    }
+
     let dir = tempdir()?;
-    let archive_path = download_to_with_name(dir.path(), url, token)?;
-    println!("🗜️extracting -> {}", dest_root.display());
+    let archive_path = dir.path().join(entry.file_name());
+    fs::rename(entry.path(), &archive_path)?;
+    progress!(
+        "🗜️extracting {} -> {}",
+        file_name(&archive_path),
+        dest_root.display()
+    );
     extract_archive(&archive_path, dest_root)?;
+    flatten_single_top_dir(dest_root)
+}
+
+/// Extract a local archive, or copy a local directory, to `dest_root`.
+pub fn stage_local(
+    path: &Path,
+    dest_root: &Path,
+    on_collision: CollisionPolicy,
+) -> anyhow::Result<FetchedAsset> {
+    if path.is_dir() {
+        copy_dir_recursive(path, dest_root, on_collision)?;
+        return Ok(FetchedAsset {
+            name: file_name(path),
+            sha256: None,
+        });
+    }
+    if !path.is_file() {
+        anyhow::bail!("{} does not exist", path.display());
+    }
+    extract_local_archive(path, dest_root)
+}
+
+fn extract_local_archive(archive_path: &Path, dest_root: &Path) -> anyhow::Result<FetchedAsset> {
+    let asset = FetchedAsset {
+        name: file_name(archive_path),
+        sha256: Some(sha256_file(archive_path)?),
+    };
+    progress!("🗜️extracting -> {}", dest_root.display());
+    extract_archive(archive_path, dest_root)?;
     flatten_single_top_dir(dest_root)?;
-    Ok(())
+    Ok(asset)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
+
+pub fn sha256_file(path: &Path) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
 }
 
 /// HACK: If there's exactly one top-level directory, move its contents up one level (to dest_root).
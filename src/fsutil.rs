@@ -1,15 +1,23 @@
+use crate::output::{Event, emit, progress, verbose, warning};
+use crate::yini::SizeBudget;
 use anyhow::{Context, anyhow};
+use clap::ValueEnum;
 use glob::glob;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
+use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
-use tracing::warn;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 use walkdir::WalkDir;
 
 fn norm_component(s: &str) -> &str {
     s.trim_matches('/').trim()
 }
 
-fn has_glob_meta(s: &str) -> bool {
+pub fn has_glob_meta(s: &str) -> bool {
     s.contains('*') || s.contains('?') || s.contains('[') || s.contains('{')
 }
 
@@ -17,113 +25,514 @@ fn has_glob_meta(s: &str) -> bool {
 pub struct CopyMapping {
     pub from: PathBuf,
     pub to: PathBuf,
+    pub mode: CopyMode,
+    /// gitignore-style patterns, in addition to the global excludes.
+    pub exclude: Vec<String>,
+}
+
+/// How `to` is used, `mode` in a copy mapping.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum CopyMode {
+    /// `to` is a directory: files keep their name, directories are copied without their own name.
+    #[default]
+    IntoDir,
+    /// `to` is the exact new path of a single file or directory.
+    Rename,
+    /// `to` is a directory: files and directories both keep their name.
+    KeepDirName,
+}
+
+impl CopyMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            CopyMode::IntoDir => "into-dir",
+            CopyMode::Rename => "rename",
+            CopyMode::KeepDirName => "keep-dir-name",
+        }
+    }
+}
+
+impl FromStr for CopyMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "into-dir" => Ok(CopyMode::IntoDir),
+            "rename" => Ok(CopyMode::Rename),
+            "keep-dir-name" => Ok(CopyMode::KeepDirName),
+            _ => Err(anyhow!(
+                "unknown mode '{s}', expected `into-dir`, `rename` or `keep-dir-name`"
+            )),
+        }
+    }
+}
+
+/// OS and editor junk that is never copied, unless `default_excludes false`.
+pub const DEFAULT_EXCLUDES: &[&str] = &[
+    ".DS_Store",
+    "._*",
+    ".Spotlight-V100/",
+    ".Trashes/",
+    "__MACOSX/",
+    "Thumbs.db",
+    "ehthumbs.db",
+    "desktop.ini",
+    "$RECYCLE.BIN/",
+    "*~",
+    "*.swp",
+    "*.swo",
+    ".#*",
+    "#*#",
+];
+
+/// gitignore-style exclude patterns: the content excludes, matched relative to the root of
+/// the copied tree (the content repo), and a mapping's own, matched relative to its source.
+pub struct Excludes {
+    layers: Vec<ExcludeLayer>,
+}
+
+struct ExcludeLayer {
+    root: PathBuf,
+    /// `root` with `.`, `..` and symlinks resolved, for paths that spell it differently.
+    canonical_root: Option<PathBuf>,
+    matcher: Gitignore,
+}
+
+impl Excludes {
+    pub fn new<'a>(
+        root: &Path,
+        patterns: impl IntoIterator<Item = &'a str>,
+    ) -> anyhow::Result<Self> {
+        Self { layers: Vec::new() }.and(root, patterns)
+    }
+
+    /// Also exclude `patterns`, matched relative to `root`. They win over the earlier ones,
+    /// `!pattern` keeps a file they exclude.
+    pub fn and<'a>(
+        mut self,
+        root: &Path,
+        patterns: impl IntoIterator<Item = &'a str>,
+    ) -> anyhow::Result<Self> {
+        // matched against paths made relative to `root` by `matches`, so that anchored
+        // patterns (`/raw`) work however `root` is written
+        let mut builder = GitignoreBuilder::new(".");
+        for pattern in patterns {
+            builder
+                .add_line(None, pattern)
+                .with_context(|| format!("invalid exclude pattern {pattern:?}"))?;
+        }
+        self.layers.push(ExcludeLayer {
+            root: root.to_path_buf(),
+            canonical_root: root.canonicalize().ok(),
+            matcher: builder.build()?,
+        });
+        Ok(self)
+    }
+
+    /// Some `!pattern` may keep a file below an excluded directory, so the directory can not
+    /// be skipped as a whole.
+    fn can_keep_below(&self) -> bool {
+        self.layers
+            .iter()
+            .any(|layer| layer.matcher.num_whitelists() > 0)
+    }
+
+    /// `path` is excluded. Paths outside of the roots never are.
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        let mut excluded = false;
+        for layer in &self.layers {
+            let Some(rel) = layer.relative(path) else {
+                continue;
+            };
+            let matched = layer.matcher.matched_path_or_any_parents(rel, is_dir);
+            if matched.is_ignore() {
+                excluded = true;
+            } else if matched.is_whitelist() {
+                excluded = false;
+            }
+        }
+        excluded
+    }
+}
+
+impl ExcludeLayer {
+    fn relative(&self, path: &Path) -> Option<PathBuf> {
+        if let Ok(rel) = path.strip_prefix(&self.root) {
+            return Some(rel.to_path_buf());
+        }
+        let root = self.canonical_root.as_ref()?;
+        let path = path.canonicalize().ok()?;
+        path.strip_prefix(root).ok().map(Path::to_path_buf)
+    }
 }
 
 /// Copy mappings where each entry specifies a source path (file/dir/glob) relative to `src_root`
-/// and a destination directory relative to `dest_root`.
+/// and a destination relative to `dest_root`, used as given by the mapping's [`CopyMode`].
+/// Files matching `exclude` (for every mapping) or the mapping's own excludes are skipped.
 ///
 /// Examples:
 ///   "data/something.p*" -> "new_data/"
 ///   "data/just_dir/" -> "another_data/"
 ///   "data/just_dir/another*.txt" -> "another_data/"
-#[allow(dead_code)]
+///   "config/release.ini" -> "data/game.ini" (rename)
+///   "data/just_dir" -> "another_data/" (keep-dir-name, ends up in another_data/just_dir/)
 pub fn copy_mappings(
     src_root: &Path,
     dest_root: &Path,
     mappings: &[CopyMapping],
+    exclude: &[String],
+    on_collision: CollisionPolicy,
 ) -> anyhow::Result<()> {
+    let mut destinations = Destinations::new(dest_root);
     for mapping in mappings {
-        let from_norm = norm_component(mapping.from.to_str().unwrap());
-        let to_norm = norm_component(mapping.to.to_str().unwrap());
-        if from_norm.is_empty() {
-            continue;
+        let excludes = Excludes::new(src_root, exclude.iter().map(String::as_str))?.and(
+            &mapping_root(src_root, mapping),
+            mapping.exclude.iter().map(String::as_str),
+        )?;
+        let counts = copy_mapping(src_root, dest_root, mapping, &excludes, &mut destinations)
+            .with_context(|| format!("copying {:?}", mapping.from))?;
+        if counts.excluded > 0 {
+            verbose!(
+                "  🚫 {}: {} excluded",
+                mapping.from.display(),
+                counts.excluded
+            );
         }
-        let abs_from = src_root.join(from_norm);
-        let abs_to_root = dest_root.join(to_norm);
-
-        // Ensure destination directory exists
-        fs::create_dir_all(&abs_to_root)?;
-
-        if has_glob_meta(from_norm) {
-            let pattern = abs_from.to_string_lossy().to_string();
-            let entries = glob(&pattern).with_context(|| format!("invalid glob: {pattern}"))?;
-            let mut matched_any = false;
-            for entry in entries {
-                matched_any = true;
-                let path = entry?;
-                if path.is_dir() {
-                    // Copy all files inside the matched directory into `to`, dropping the top-level dir name
-                    for e in WalkDir::new(&path) {
-                        let e = e?;
-                        if e.file_type().is_dir() {
-                            continue;
-                        }
-                        let rel = e.path().strip_prefix(&path).unwrap();
-                        let out = abs_to_root.join(rel);
-                        if let Some(parent) = out.parent() {
-                            fs::create_dir_all(parent)?;
-                        }
-                        fs::copy(e.path(), &out).with_context(|| {
-                            format!("copy {} -> {}", e.path().display(), out.display())
-                        })?;
-                    }
-                } else if path.is_file() {
-                    let base = path.parent().unwrap();
-                    let rel = path.strip_prefix(base).unwrap();
-                    let out = abs_to_root.join(rel);
-                    if let Some(parent) = out.parent() {
-                        fs::create_dir_all(parent)?;
-                    }
-                    fs::copy(&path, &out)
-                        .with_context(|| format!("copy {} -> {}", path.display(), out.display()))?;
-                }
-            }
-            if !matched_any {
-                warn!(?from_norm, ?src_root, "no matches for glob");
+        emit(Event::FilesCopied {
+            from: mapping.from.clone(),
+            to: mapping.to.clone(),
+            files: counts.copied,
+            excluded: counts.excluded,
+        });
+    }
+    destinations.finish(on_collision)
+}
+
+/// Where the mapping's own excludes are anchored: its source directory, the directory before
+/// the first glob (`levels` for `levels/*/data`), or the directory of a single file.
+fn mapping_root(src_root: &Path, mapping: &CopyMapping) -> PathBuf {
+    let from = norm_component(mapping.from.to_str().unwrap());
+    let prefix: Vec<&str> = from
+        .split('/')
+        .take_while(|component| !has_glob_meta(component))
+        .collect();
+    let root = src_root.join(prefix.join("/"));
+    if has_glob_meta(from) || root.is_dir() {
+        root
+    } else {
+        root.parent().unwrap_or(src_root).to_path_buf()
+    }
+}
+
+#[derive(Debug, Default)]
+struct CopyCounts {
+    copied: usize,
+    excluded: usize,
+}
+
+/// Copy a single mapping, returns the number of files copied and excluded.
+fn copy_mapping(
+    src_root: &Path,
+    dest_root: &Path,
+    mapping: &CopyMapping,
+    excludes: &Excludes,
+    destinations: &mut Destinations,
+) -> anyhow::Result<CopyCounts> {
+    let mut counts = CopyCounts::default();
+
+    let from_norm = norm_component(mapping.from.to_str().unwrap());
+    let to_norm = norm_component(mapping.to.to_str().unwrap());
+    if from_norm.is_empty() {
+        return Ok(counts);
+    }
+    let abs_from = src_root.join(from_norm);
+    let abs_to_root = dest_root.join(to_norm);
+
+    if mapping.mode == CopyMode::Rename {
+        return copy_renamed(
+            src_root,
+            &abs_from,
+            &abs_to_root,
+            mapping,
+            excludes,
+            destinations,
+        );
+    }
+
+    // Ensure destination directory exists
+    fs::create_dir_all(&abs_to_root)?;
+
+    if has_glob_meta(from_norm) {
+        let pattern = abs_from.to_string_lossy().to_string();
+        let entries = glob(&pattern).with_context(|| format!("invalid glob: {pattern}"))?;
+        let mut matched_any = false;
+        for entry in entries {
+            matched_any = true;
+            let path = entry?;
+            if path.is_dir() {
+                // Copy all files inside the matched directory into `to`, dropping the top-level dir
+                // name unless keep-dir-name
+                let out_root = match mapping.mode {
+                    CopyMode::KeepDirName => abs_to_root.join(path.file_name().unwrap()),
+                    _ => abs_to_root.clone(),
+                };
+                copy_tree(
+                    src_root,
+                    &path,
+                    &out_root,
+                    excludes,
+                    destinations,
+                    &mut counts,
+                )?;
+            } else if path.is_file() {
+                let base = path.parent().unwrap();
+                let rel = path.strip_prefix(base).unwrap();
+                copy_file(
+                    src_root,
+                    &path,
+                    &abs_to_root.join(rel),
+                    excludes,
+                    destinations,
+                    &mut counts,
+                )?;
             }
-            continue;
         }
+        if !matched_any {
+            warning(format!("no matches for glob {from_norm:?} in {src_root:?}"));
+        }
+        return Ok(counts);
+    }
 
-        if abs_from.is_dir() {
-            // Copy all files inside the directory into `to`
-            for e in WalkDir::new(&abs_from) {
-                let e = e?;
-                if e.file_type().is_dir() {
-                    continue;
-                }
-                let rel = e.path().strip_prefix(&abs_from).unwrap();
-                let out = abs_to_root.join(rel);
-                if let Some(parent) = out.parent() {
-                    fs::create_dir_all(parent)?;
-                }
-                fs::copy(e.path(), &out)
-                    .with_context(|| format!("copy {} -> {}", e.path().display(), out.display()))?;
-            }
-            continue;
+    if abs_from.is_dir() {
+        // Copy all files inside the directory into `to`, or into `to/<dir name>`
+        let out_root = match mapping.mode {
+            CopyMode::KeepDirName => abs_to_root.join(abs_from.file_name().unwrap()),
+            _ => abs_to_root,
+        };
+        copy_tree(
+            src_root,
+            &abs_from,
+            &out_root,
+            excludes,
+            destinations,
+            &mut counts,
+        )?;
+        return Ok(counts);
+    }
+
+    if abs_from.is_file() {
+        if mapping.mode == CopyMode::KeepDirName {
+            return Err(anyhow!(
+                "{from_norm} is a file, keep-dir-name only applies to directories (use into-dir)"
+            ));
         }
+        let file_name = abs_from.file_name().unwrap();
+        copy_file(
+            src_root,
+            &abs_from,
+            &abs_to_root.join(file_name),
+            excludes,
+            destinations,
+            &mut counts,
+        )?;
+        return Ok(counts);
+    }
+
+    warning(format!("path not found {from_norm:?} in {src_root:?}"));
+    Ok(counts)
+}
 
-        if abs_from.is_file() {
-            let file_name = abs_from.file_name().unwrap();
-            let out = abs_to_root.join(file_name);
-            if let Some(parent) = out.parent() {
-                fs::create_dir_all(parent)?;
+/// `rename`: `to` is the new path of the single file or directory `abs_from`.
+fn copy_renamed(
+    src_root: &Path,
+    abs_from: &Path,
+    abs_to: &Path,
+    mapping: &CopyMapping,
+    excludes: &Excludes,
+    destinations: &mut Destinations,
+) -> anyhow::Result<CopyCounts> {
+    let mut counts = CopyCounts::default();
+    let from = mapping.from.to_string_lossy();
+    let to = mapping.to.to_string_lossy();
+
+    if norm_component(&to).is_empty() {
+        return Err(anyhow!("rename needs a destination path"));
+    }
+
+    if abs_from.is_dir() {
+        fs::create_dir_all(abs_to)?;
+        copy_tree(
+            src_root,
+            abs_from,
+            abs_to,
+            excludes,
+            destinations,
+            &mut counts,
+        )?;
+        return Ok(counts);
+    }
+
+    if abs_from.is_file() {
+        if to.ends_with('/') {
+            return Err(anyhow!(
+                "{from:?} is a file but {to:?} is a directory, use into-dir to keep the file name"
+            ));
+        }
+        if abs_to.is_dir() {
+            return Err(anyhow!(
+                "can not rename {from:?} to {to:?}, a directory with that name was already copied"
+            ));
+        }
+        copy_file(
+            src_root,
+            abs_from,
+            abs_to,
+            excludes,
+            destinations,
+            &mut counts,
+        )?;
+        return Ok(counts);
+    }
+
+    warning(format!("path not found {from:?} in {src_root:?}"));
+    Ok(counts)
+}
+
+/// Copy the files below `dir` into `out_root`, skipping excluded files and directories.
+fn copy_tree(
+    src_root: &Path,
+    dir: &Path,
+    out_root: &Path,
+    excludes: &Excludes,
+    destinations: &mut Destinations,
+    counts: &mut CopyCounts,
+) -> anyhow::Result<()> {
+    let mut entries = WalkDir::new(dir).into_iter();
+    while let Some(e) = entries.next() {
+        let e = e?;
+        if e.file_type().is_dir() {
+            if e.depth() > 0 && !excludes.can_keep_below() && excludes.matches(e.path(), true) {
+                report_excluded(src_root, e.path());
+                counts.excluded += WalkDir::new(e.path())
+                    .into_iter()
+                    .filter_map(Result::ok)
+                    .filter(|e| !e.file_type().is_dir())
+                    .count();
+                entries.skip_current_dir();
             }
-            fs::copy(&abs_from, &out)
-                .with_context(|| format!("copy {} -> {}", abs_from.display(), out.display()))?;
             continue;
         }
+        let rel = e.path().strip_prefix(dir).unwrap();
+        copy_file(
+            src_root,
+            e.path(),
+            &out_root.join(rel),
+            excludes,
+            destinations,
+            counts,
+        )?;
+    }
+    Ok(())
+}
 
-        warn!(?from_norm, ?src_root, "path not found");
+fn copy_file(
+    src_root: &Path,
+    from: &Path,
+    out: &Path,
+    excludes: &Excludes,
+    destinations: &mut Destinations,
+    counts: &mut CopyCounts,
+) -> anyhow::Result<()> {
+    if excludes.matches(from, false) {
+        report_excluded(src_root, from);
+        counts.excluded += 1;
+        return Ok(());
+    }
+    destinations.record(out, from, from.strip_prefix(src_root).unwrap_or(from))?;
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    stage_file(from, out)?;
+    counts.copied += 1;
+    Ok(())
+}
+
+fn report_excluded(src_root: &Path, path: &Path) {
+    let rel = path.strip_prefix(src_root).unwrap_or(path);
+    verbose!("    🚫 {}", rel.display());
+}
+
+/// How staged files are put into the build dir.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum StagingStrategy {
+    /// Copy every file
+    #[default]
+    Copy,
+    /// Hard link, copy if that fails (e.g. across filesystems)
+    Hardlink,
+    /// Copy-on-write clone (APFS, Btrfs, XFS, ReFS), copy if that fails
+    Reflink,
+    /// Reflink, else hard link, else copy
+    Auto,
+}
+
+static STAGING: OnceLock<StagingStrategy> = OnceLock::new();
+
+static FELL_BACK: AtomicBool = AtomicBool::new(false);
+
+pub fn init_staging(strategy: StagingStrategy) {
+    let _ = STAGING.set(strategy);
+}
+
+/// Put `from` at `to` using the staging strategy. An existing `to` is removed first, so a
+/// hard linked file is never written through.
+pub fn stage_file(from: &Path, to: &Path) -> anyhow::Result<()> {
+    stage_with(STAGING.get().copied().unwrap_or_default(), from, to)
+}
+
+fn stage_with(strategy: StagingStrategy, from: &Path, to: &Path) -> anyhow::Result<()> {
+    if to.symlink_metadata().is_ok() {
+        fs::remove_file(to).with_context(|| format!("removing {}", to.display()))?;
+    }
+    let linked = match strategy {
+        StagingStrategy::Copy => Err(None),
+        StagingStrategy::Hardlink => fs::hard_link(from, to).map_err(Some),
+        StagingStrategy::Reflink => reflink_copy::reflink(from, to).map_err(Some),
+        StagingStrategy::Auto => reflink_copy::reflink(from, to)
+            .or_else(|_| fs::hard_link(from, to))
+            .map_err(Some),
+    };
+    if let Err(err) = linked {
+        if let Some(err) = err
+            && !FELL_BACK.swap(true, Ordering::Relaxed)
+        {
+            verbose!(
+                "  📄 can not {} {} -> {} ({err}), copying instead",
+                strategy.to_possible_value().unwrap().get_name(),
+                from.display(),
+                to.display()
+            );
+        }
+        fs::copy(from, to)
+            .with_context(|| format!("copy {} -> {}", from.display(), to.display()))?;
     }
     Ok(())
 }
 
 /// Copy a directory tree: src_dir -> dest_dir (dest_dir becomes/contains the contents of src_dir)
-pub fn copy_dir_recursive(src_dir: &Path, dest_dir: &Path) -> anyhow::Result<()> {
-    println!("📂copying directory {src_dir:?} -> {dest_dir:?}");
+/// Files already in `dest_dir` that would be overwritten or differ only in case are collisions.
+pub fn copy_dir_recursive(
+    src_dir: &Path,
+    dest_dir: &Path,
+    on_collision: CollisionPolicy,
+) -> anyhow::Result<()> {
+    progress!("📂copying directory {src_dir:?} -> {dest_dir:?}");
     if !src_dir.exists() {
         return Err(anyhow!("missing: {}", src_dir.display()));
     }
+    let mut destinations = Destinations::new(dest_dir);
+    destinations.record_existing()?;
+    let mut files = 0;
     for entry in WalkDir::new(src_dir) {
         let entry = entry?;
         if entry.file_type().is_dir() {
@@ -131,21 +540,579 @@ pub fn copy_dir_recursive(src_dir: &Path, dest_dir: &Path) -> anyhow::Result<()>
         }
         let rel = entry.path().strip_prefix(src_dir).unwrap();
         let out_path = dest_dir.join(rel);
+        destinations.record(&out_path, entry.path(), entry.path())?;
         if let Some(parent) = out_path.parent() {
             fs::create_dir_all(parent)?;
         }
-        fs::copy(entry.path(), &out_path).with_context(|| {
-            format!("copy {} -> {}", entry.path().display(), out_path.display())
-        })?;
+        stage_file(entry.path(), &out_path)?;
+        files += 1;
+    }
+    emit(Event::FilesCopied {
+        from: src_dir.to_path_buf(),
+        to: dest_dir.to_path_buf(),
+        files,
+        excluded: 0,
+    });
+    destinations.finish(on_collision)
+}
+
+/// What to do when two files end up at the same path in a depot, or at paths that only differ
+/// in case. `on_collision` in the yini.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum CollisionPolicy {
+    #[default]
+    Error,
+    Warn,
+}
+
+impl CollisionPolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            CollisionPolicy::Error => "error",
+            CollisionPolicy::Warn => "warn",
+        }
+    }
+}
+
+impl FromStr for CollisionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(CollisionPolicy::Error),
+            "warn" => Ok(CollisionPolicy::Warn),
+            _ => Err(anyhow!("expected `error` or `warn`, got '{s}'")),
+        }
     }
-    Ok(())
 }
 
-/// Remove a directory if it exists, then recreate it (empty)
-pub fn clean_dir(dir: &Path) -> anyhow::Result<()> {
-    if dir.exists() {
-        fs::remove_dir_all(dir).with_context(|| format!("removing {}", dir.display()))?;
+struct Written {
+    source: PathBuf,
+    /// How the source is shown, e.g. relative to the content repo.
+    label: PathBuf,
+}
+
+/// Every path written below `root`, to find overwrites and paths that would clash on
+/// case-insensitive filesystems (Windows, macOS).
+struct Destinations {
+    root: PathBuf,
+    written: HashMap<PathBuf, Written>,
+    /// Lowercased file or directory path -> the first path with that spelling.
+    folded: HashMap<String, PathBuf>,
+    reported: BTreeSet<String>,
+    problems: Vec<String>,
+}
+
+impl Destinations {
+    fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            written: HashMap::new(),
+            folded: HashMap::new(),
+            reported: BTreeSet::new(),
+            problems: Vec::new(),
+        }
+    }
+
+    /// Files that are already below the root, e.g. an extracted release archive.
+    fn record_existing(&mut self) -> anyhow::Result<()> {
+        let root = self.root.clone();
+        for entry in WalkDir::new(&root).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_dir() {
+                self.record(entry.path(), entry.path(), entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, out: &Path, source: &Path, label: &Path) -> anyhow::Result<()> {
+        let dest = out.strip_prefix(&self.root).unwrap_or(out).to_path_buf();
+
+        if let Some(previous) = self.written.get(&dest) {
+            if previous.source != source && !same_contents(&previous.source, source)? {
+                self.problems.push(format!(
+                    "{} is written by both {} and {}",
+                    dest.display(),
+                    previous.label.display(),
+                    label.display()
+                ));
+            }
+            return Ok(());
+        }
+
+        // the file and every directory leading to it
+        let mut prefix = PathBuf::new();
+        for component in dest.components() {
+            prefix.push(component);
+            let key = prefix.to_string_lossy().to_lowercase();
+            match self.folded.get(&key) {
+                Some(existing) if *existing != prefix => {
+                    if self.reported.insert(key) {
+                        let existing_label = self
+                            .written
+                            .iter()
+                            .find(|(path, _)| path.starts_with(existing))
+                            .map(|(_, written)| written.label.display().to_string())
+                            .unwrap_or_default();
+                        self.problems.push(format!(
+                            "{} (from {}) and {} (from {}) differ only in case",
+                            existing.display(),
+                            existing_label,
+                            prefix.display(),
+                            label.display()
+                        ));
+                    }
+                    break;
+                }
+                Some(_) => {}
+                None => {
+                    self.folded.insert(key, prefix.clone());
+                }
+            }
+        }
+
+        self.written.insert(
+            dest,
+            Written {
+                source: source.to_path_buf(),
+                label: label.to_path_buf(),
+            },
+        );
+        Ok(())
+    }
+
+    fn finish(self, policy: CollisionPolicy) -> anyhow::Result<()> {
+        if self.problems.is_empty() {
+            return Ok(());
+        }
+        match policy {
+            CollisionPolicy::Warn => {
+                for problem in self.problems {
+                    warning(format!("{}: {problem}", self.root.display()));
+                }
+                Ok(())
+            }
+            CollisionPolicy::Error => Err(anyhow!(
+                "conflicting files in {} (set `on_collision warn` to allow):\n  {}",
+                self.root.display(),
+                self.problems.join("\n  ")
+            )),
+        }
+    }
+}
+
+pub fn same_contents(a: &Path, b: &Path) -> anyhow::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let mut reader_a = BufReader::new(fs::File::open(a)?);
+    let mut reader_b = BufReader::new(fs::File::open(b)?);
+    let mut buf_a = [0u8; 64 * 1024];
+    let mut buf_b = [0u8; 64 * 1024];
+    loop {
+        let read = reader_a.read(&mut buf_a)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        reader_b.read_exact(&mut buf_b[..read])?;
+        if buf_a[..read] != buf_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Files below a directory, largest first.
+#[derive(Debug, Default)]
+pub struct DirSize {
+    pub bytes: u64,
+    pub files: Vec<(PathBuf, u64)>,
+}
+
+pub fn dir_size(dir: &Path) -> anyhow::Result<DirSize> {
+    let mut size = DirSize::default();
+    for entry in WalkDir::new(dir) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let len = entry.metadata()?.len();
+        size.bytes += len;
+        let rel = entry.path().strip_prefix(dir).unwrap();
+        size.files.push((rel.to_path_buf(), len));
+    }
+    size.files
+        .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(size)
+}
+
+/// Number of largest files listed in the size report.
+const LARGEST_FILES: usize = 5;
+
+/// Check a staged depot against its `max_size` and `max_file_size`, listing the largest files.
+pub fn check_size_budget(name: &str, dir: &Path, budget: &SizeBudget) -> anyhow::Result<()> {
+    if !budget.is_set() {
+        return Ok(());
+    }
+    let size = dir_size(dir)?;
+    progress!(
+        "⚖️{name}: {} in {} files",
+        format_size(size.bytes),
+        size.files.len()
+    );
+    for (path, len) in size.files.iter().take(LARGEST_FILES) {
+        progress!("  {:>10} {}", format_size(*len), path.display());
+    }
+
+    let mut problems = Vec::new();
+    if let Some(max_size) = budget.max_size
+        && size.bytes > max_size
+    {
+        problems.push(format!(
+            "{} is over max_size {}",
+            format_size(size.bytes),
+            format_size(max_size)
+        ));
+    }
+    if let Some(max_file_size) = budget.max_file_size {
+        for (path, len) in size.files.iter().filter(|(_, len)| *len > max_file_size) {
+            problems.push(format!(
+                "{} is {}, over max_file_size {}",
+                path.display(),
+                format_size(*len),
+                format_size(max_file_size)
+            ));
+        }
+    }
+    if !problems.is_empty() {
+        return Err(anyhow!(
+            "{name} depot is over budget:\n  {}",
+            problems.join("\n  ")
+        ));
     }
-    fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
     Ok(())
 }
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excludes(root: &Path) -> Excludes {
+        Excludes::new(root, ["/raw", "cache/", "*.psd"]).unwrap()
+    }
+
+    #[test]
+    fn anchored_patterns_match_below_a_relative_root() {
+        let root = Path::new("./temp/data");
+        let excludes = excludes(root);
+        assert!(excludes.matches(&root.join("raw"), true));
+        assert!(excludes.matches(&root.join("raw/a.png"), false));
+        assert!(!excludes.matches(&root.join("art/raw"), true));
+        assert!(excludes.matches(&root.join("art/cache"), true));
+        assert!(excludes.matches(&root.join("art/a.psd"), false));
+        assert!(!excludes.matches(&root.join("art/a.png"), false));
+    }
+
+    #[test]
+    fn matches_the_root_spelled_differently() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("data/raw")).unwrap();
+        let excludes = excludes(&dir.path().join("data/../data"));
+        assert!(excludes.matches(&dir.path().join("data/raw"), true));
+        assert!(!excludes.matches(&dir.path().join("data"), true));
+    }
+
+    #[test]
+    fn paths_outside_the_root_are_not_excluded() {
+        let excludes = excludes(Path::new("temp/data"));
+        assert!(!excludes.matches(Path::new("/elsewhere/raw/a.psd"), false));
+        assert!(!excludes.matches(Path::new("other/raw"), true));
+    }
+
+    /// Write `files` (relative paths) below `root`.
+    fn write_files(root: &Path, files: &[&str]) {
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        }
+    }
+
+    /// Relative paths of the files below `root`.
+    fn list_files(root: &Path) -> Vec<String> {
+        let mut files: Vec<String> = WalkDir::new(root)
+            .into_iter()
+            .map(Result::unwrap)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                let rel = entry.path().strip_prefix(root).unwrap();
+                rel.to_string_lossy().replace('\\', "/")
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn mapping(from: &str, to: &str, exclude: &[&str]) -> CopyMapping {
+        CopyMapping {
+            from: PathBuf::from(from),
+            to: PathBuf::from(to),
+            mode: CopyMode::default(),
+            exclude: exclude.iter().map(|pattern| pattern.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn negation_keeps_a_file_below_an_excluded_dir() {
+        let root = Path::new("content");
+        let excludes = Excludes::new(root, ["raw/", "!raw/credits.txt"]).unwrap();
+        assert!(excludes.can_keep_below());
+        assert!(excludes.matches(&root.join("raw"), true));
+        assert!(excludes.matches(&root.join("raw/a.png"), false));
+        assert!(!excludes.matches(&root.join("raw/credits.txt"), false));
+    }
+
+    #[test]
+    fn later_patterns_win() {
+        let excludes = Excludes::new(Path::new("content"), ["*.txt"])
+            .unwrap()
+            .and(Path::new("content/assets"), ["!/notes.txt"])
+            .unwrap();
+        assert!(!excludes.matches(Path::new("content/assets/notes.txt"), false));
+        assert!(excludes.matches(Path::new("content/assets/sub/notes.txt"), false));
+        assert!(excludes.matches(Path::new("content/notes.txt"), false));
+    }
+
+    #[test]
+    fn mapping_excludes_are_anchored_at_the_source() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        write_files(
+            src.path(),
+            &[
+                "assets/raw/credits.txt",
+                "assets/raw/a.png",
+                "assets/b.png",
+                "raw/credits.txt",
+            ],
+        );
+        copy_mappings(
+            src.path(),
+            dest.path(),
+            &[mapping(
+                "assets",
+                "data/assets",
+                &["raw/", "!raw/credits.txt"],
+            )],
+            &[],
+            CollisionPolicy::Error,
+        )
+        .unwrap();
+        assert_eq!(
+            list_files(dest.path()),
+            ["data/assets/b.png", "data/assets/raw/credits.txt"]
+        );
+    }
+
+    #[test]
+    fn content_excludes_are_anchored_at_the_root() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        write_files(
+            src.path(),
+            &["assets/raw/a.png", "assets/b.psd", "assets/c.png"],
+        );
+        copy_mappings(
+            src.path(),
+            dest.path(),
+            &[mapping("assets", "data", &[])],
+            &["/assets/raw/".to_string(), "*.psd".to_string()],
+            CollisionPolicy::Error,
+        )
+        .unwrap();
+        assert_eq!(list_files(dest.path()), ["data/c.png"]);
+    }
+
+    /// A release archive already extracted to `dest`, and a redist dir to copy on top of it.
+    fn archive_and_redist(
+        archive: &[(&str, &str)],
+        redist: &[(&str, &str)],
+    ) -> (tempfile::TempDir, tempfile::TempDir) {
+        let dest = tempfile::tempdir().unwrap();
+        let redist_dir = tempfile::tempdir().unwrap();
+        for (root, files) in [(dest.path(), archive), (redist_dir.path(), redist)] {
+            for (file, contents) in files {
+                let path = root.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+        }
+        (dest, redist_dir)
+    }
+
+    #[test]
+    fn redist_overwriting_an_archive_file_is_an_error_by_default() {
+        let (dest, redist) = archive_and_redist(
+            &[("bin/steam_api.dll", "old")],
+            &[("bin/steam_api.dll", "new")],
+        );
+        let err = copy_dir_recursive(redist.path(), dest.path(), CollisionPolicy::default())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("bin/steam_api.dll is written by both"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn redist_overwrites_an_archive_file_with_warn() {
+        let (dest, redist) = archive_and_redist(
+            &[("bin/steam_api.dll", "old")],
+            &[("bin/steam_api.dll", "new")],
+        );
+        copy_dir_recursive(redist.path(), dest.path(), CollisionPolicy::Warn).unwrap();
+        assert_eq!(
+            fs::read_to_string(dest.path().join("bin/steam_api.dll")).unwrap(),
+            "new"
+        );
+    }
+
+    #[test]
+    fn identical_files_do_not_collide() {
+        let (dest, redist) = archive_and_redist(
+            &[("bin/steam_api.dll", "same")],
+            &[("bin/steam_api.dll", "same")],
+        );
+        copy_dir_recursive(redist.path(), dest.path(), CollisionPolicy::Error).unwrap();
+    }
+
+    #[test]
+    fn paths_that_differ_only_in_case_collide() {
+        let (dest, redist) = archive_and_redist(&[("Data/a.txt", "a")], &[("data/b.txt", "b")]);
+        let err = copy_dir_recursive(redist.path(), dest.path(), CollisionPolicy::Error)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("differ only in case"), "{err}");
+
+        let (dest, redist) = archive_and_redist(&[("Data/a.txt", "a")], &[("data/b.txt", "b")]);
+        copy_dir_recursive(redist.path(), dest.path(), CollisionPolicy::Warn).unwrap();
+    }
+
+    #[test]
+    fn two_mappings_writing_one_file_collide() {
+        let src = tempfile::tempdir().unwrap();
+        write_files(src.path(), &["a/config.ini", "b/config.ini"]);
+        let mappings = [mapping("a", "data", &[]), mapping("b", "data", &[])];
+
+        let dest = tempfile::tempdir().unwrap();
+        let err = copy_mappings(
+            src.path(),
+            dest.path(),
+            &mappings,
+            &[],
+            CollisionPolicy::Error,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("data/config.ini is written by both"), "{err}");
+
+        let dest = tempfile::tempdir().unwrap();
+        copy_mappings(
+            src.path(),
+            dest.path(),
+            &mappings,
+            &[],
+            CollisionPolicy::Warn,
+        )
+        .unwrap();
+        assert_eq!(list_files(dest.path()), ["data/config.ini"]);
+    }
+
+    #[cfg(unix)]
+    fn inode(path: &Path) -> (u64, u64) {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = fs::metadata(path).unwrap();
+        (metadata.dev(), metadata.ino())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hardlink_staging_links_and_never_writes_through() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("from.bin"), dir.path().join("to.bin"));
+        fs::write(&from, "new").unwrap();
+        fs::write(&to, "old").unwrap();
+
+        stage_with(StagingStrategy::Hardlink, &from, &to).unwrap();
+        assert_eq!(inode(&from), inode(&to));
+        assert_eq!(fs::read_to_string(&to).unwrap(), "new");
+
+        // staging again replaces the link instead of writing into the source
+        let other = dir.path().join("other.bin");
+        fs::write(&other, "other").unwrap();
+        stage_with(StagingStrategy::Hardlink, &other, &to).unwrap();
+        assert_eq!(fs::read_to_string(&from).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&to).unwrap(), "other");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_staging_makes_a_separate_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("from.bin"), dir.path().join("to.bin"));
+        fs::write(&from, "data").unwrap();
+
+        stage_with(StagingStrategy::Copy, &from, &to).unwrap();
+        assert_ne!(inode(&from), inode(&to));
+        assert_eq!(fs::read_to_string(&to).unwrap(), "data");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hardlink_across_filesystems_falls_back_to_a_copy() {
+        let shm = Path::new("/dev/shm");
+        let Ok(other_fs) = tempfile::tempdir_in(shm) else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        if inode(other_fs.path()).0 == inode(dir.path()).0 {
+            return;
+        }
+        let (from, to) = (other_fs.path().join("from.bin"), dir.path().join("to.bin"));
+        fs::write(&from, "data").unwrap();
+
+        stage_with(StagingStrategy::Hardlink, &from, &to).unwrap();
+        assert_ne!(inode(&from), inode(&to));
+        assert_eq!(fs::read_to_string(&to).unwrap(), "data");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reflink_staging_never_shares_the_inode() {
+        // a reflink where the filesystem supports it, a copy everywhere else
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("from.bin"), dir.path().join("to.bin"));
+        fs::write(&from, "data").unwrap();
+
+        stage_with(StagingStrategy::Reflink, &from, &to).unwrap();
+        assert_ne!(inode(&from), inode(&to));
+        fs::write(&to, "changed").unwrap();
+        assert_eq!(fs::read_to_string(&from).unwrap(), "data");
+    }
+}
@@ -1,16 +1,165 @@
-use anyhow::Context;
-use std::path::Path;
+use crate::fsutil::has_glob_meta;
+use crate::output::{progress, verbose};
+use anyhow::{Context, anyhow};
+use sha2::{Digest, Sha256};
+use std::fs::{self, TryLockError};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use walkdir::WalkDir;
 
-pub fn shallow_clone_to(repo: &str, branch: &str, dest: &Path) -> anyhow::Result<(String, String)> {
-    println!("🌱cloning {repo} @ {branch} -> {dest:?}");
+/// Which Git LFS objects to download after cloning.
+#[derive(Debug, Clone)]
+pub enum LfsFetch {
+    Off,
+    All,
+    /// Only objects below these paths or matching these globs.
+    Paths(Vec<String>),
+}
+
+/// Which submodules to check out, `submodules` in the content block.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Submodules {
+    #[default]
+    None,
+    /// The submodules of the content repo, at depth 1.
+    Shallow,
+    /// Also the submodules of submodules, at depth 1.
+    Recursive,
+}
+
+impl Submodules {
+    pub fn name(self) -> &'static str {
+        match self {
+            Submodules::None => "none",
+            Submodules::Shallow => "shallow",
+            Submodules::Recursive => "recursive",
+        }
+    }
+}
+
+impl FromStr for Submodules {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Submodules::None),
+            "shallow" => Ok(Submodules::Shallow),
+            "recursive" => Ok(Submodules::Recursive),
+            _ => Err(anyhow!(
+                "expected `none`, `shallow` or `recursive`, got '{s}'"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CloneOptions {
+    /// The copy sources (files, directories or globs) to check out, `None` for everything.
+    pub sparse_paths: Option<Vec<String>>,
+    pub submodules: Submodules,
+    pub lfs: LfsFetch,
+    /// Persistent bare mirror to fetch into and check out from, `None` to clone from scratch.
+    pub mirror: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubmoduleCommit {
+    pub path: String,
+    pub commit: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClonedCommit {
+    pub commit: String,
+    /// Author date, RFC 3339.
+    pub committed_at: String,
+    pub submodules: Vec<SubmoduleCommit>,
+}
+
+/// Clone the tip of `branch` into `dest` and remove `.git` afterwards. With sparse paths only
+/// those are checked out, and only their blobs downloaded (partial clone). With a mirror only
+/// new objects are fetched into it, and `dest` is a worktree of the mirror until the end.
+pub fn shallow_clone_to(
+    repo: &str,
+    branch: &str,
+    dest: &Path,
+    options: &CloneOptions,
+) -> anyhow::Result<ClonedCommit> {
     if dest.exists() {
         std::fs::remove_dir_all(dest).context("cleaning dest before clone")?;
     }
 
-    let status = Command::new("git")
-        .args(["clone", "--depth", "1", "--branch", branch, repo])
+    // two runs must not fetch into the mirror (or add worktrees to it) at the same time
+    let _lock = match &options.mirror {
+        Some(mirror) => Some(MirrorLock::acquire(mirror)?),
+        None => None,
+    };
+
+    // what to check out once the sparse checkout is set up
+    let rev = match &options.mirror {
+        Some(mirror) => add_mirror_worktree(mirror, repo, branch, dest, options)?,
+        None => {
+            clone(repo, branch, dest, options)?;
+            branch.to_string()
+        }
+    };
+
+    if let Some(paths) = &options.sparse_paths {
+        sparse_checkout(dest, &rev, paths)?;
+    }
+
+    let submodules = match options.submodules {
+        Submodules::None => Vec::new(),
+        submodules => update_submodules(dest, submodules)?,
+    };
+
+    // Capture the commit locally before removing .git
+    let (commit_hash, commit_time_iso) = head_commit(dest)?;
+
+    if !matches!(options.lfs, LfsFetch::Off) && uses_lfs(dest) {
+        lfs_pull(dest, &options.lfs)?;
+    }
+
+    // Remove .git (a file in a worktree), and the `.git` files of submodules
+    let git_dir = dest.join(".git");
+    if git_dir.is_dir() {
+        std::fs::remove_dir_all(&git_dir).context("removing .git")?;
+    } else if git_dir.is_file() {
+        fs::remove_file(&git_dir).context("removing .git")?;
+    }
+    for submodule in &submodules {
+        let git_file = dest.join(&submodule.path).join(".git");
+        if git_file.is_file() {
+            fs::remove_file(&git_file).context("removing submodule .git")?;
+        }
+    }
+    if let Some(mirror) = &options.mirror {
+        // forget the worktree, its directory no longer is one
+        run_git(mirror, &["worktree", "prune"])?;
+    }
+
+    Ok(ClonedCommit {
+        commit: commit_hash,
+        committed_at: commit_time_iso,
+        submodules,
+    })
+}
+
+fn clone(repo: &str, branch: &str, dest: &Path, options: &CloneOptions) -> anyhow::Result<()> {
+    progress!("🌱cloning {repo} @ {branch} -> {dest:?}");
+    let mut clone = git();
+    clone.args(["clone", "--depth", "1", "--branch", branch]);
+    if options.sparse_paths.is_some() {
+        clone.args(["--filter=blob:none", "--no-checkout"]);
+    }
+    let status = clone
+        .arg(repo)
         .arg(dest)
+        // LFS objects are pulled below, if wanted
+        .env("GIT_LFS_SKIP_SMUDGE", "1")
         .stdin(Stdio::null())
         .stdout(Stdio::null())
         .stderr(Stdio::null())
@@ -19,10 +168,135 @@ pub fn shallow_clone_to(repo: &str, branch: &str, dest: &Path) -> anyhow::Result
     if !status.success() {
         anyhow::bail!("git clone failed");
     }
+    Ok(())
+}
+
+/// Where the mirror of `repo` is kept below `cache_dir`, one bare repo per URL. The readable
+/// part of the name can be the same for different URLs (`a/b_c`, `a_b/c`), the hash is not.
+pub fn mirror_dir(cache_dir: &Path, repo: &str) -> PathBuf {
+    let url = repo.trim_end_matches('/').trim_end_matches(".git");
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let name: String = url
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    cache_dir
+        .join("git")
+        .join(format!("{name}-{}.git", &hash[..12]))
+}
+
+/// `<mirror>.lock`, locked while a run fetches into the mirror and checks out from it. The
+/// lock goes with the file handle, so it is also released when a run is killed.
+struct MirrorLock {
+    _file: fs::File,
+}
+
+impl MirrorLock {
+    fn acquire(mirror: &Path) -> anyhow::Result<Self> {
+        let mut path = mirror.as_os_str().to_owned();
+        path.push(".lock");
+        let path = PathBuf::from(path);
+        fs::create_dir_all(mirror.parent().unwrap())?;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("opening {}", path.display()))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                progress!("⏳waiting for another boiler run to finish with {mirror:?}");
+                file.lock()
+                    .with_context(|| format!("locking {}", path.display()))?;
+            }
+            Err(TryLockError::Error(err)) => {
+                return Err(err).with_context(|| format!("locking {}", path.display()));
+            }
+        }
+        Ok(Self { _file: file })
+    }
+}
+
+/// Create or update the bare mirror of `repo` and add `dest` as a detached worktree of the tip
+/// of `branch`. Returns that commit. Blobs are fetched on checkout (partial clone), so the
+/// mirror only ever downloads what was checked out at some point.
+fn add_mirror_worktree(
+    mirror: &Path,
+    repo: &str,
+    branch: &str,
+    dest: &Path,
+    options: &CloneOptions,
+) -> anyhow::Result<String> {
+    if !mirror.join("HEAD").is_file() {
+        progress!("🪞creating mirror of {repo} in {mirror:?}");
+        if mirror.exists() {
+            // left over from a clone that did not finish
+            fs::remove_dir_all(mirror).context("removing incomplete mirror")?;
+        }
+        fs::create_dir_all(mirror.parent().unwrap())?;
+        let status = git()
+            .args(["clone", "--bare", "--filter=blob:none"])
+            .arg(repo)
+            .arg(mirror)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .context("running git clone --bare")?;
+        if !status.success() {
+            anyhow::bail!("creating the mirror of {repo} failed");
+        }
+    }
 
-    // Capture the commit hash locally before removing .git
-    let output = Command::new("git")
-        .current_dir(dest)
+    progress!("🪞fetching {repo} @ {branch} into {mirror:?}");
+    run_git(
+        mirror,
+        &[
+            "fetch",
+            "--filter=blob:none",
+            "origin",
+            &format!("+refs/heads/{branch}:refs/heads/{branch}"),
+        ],
+    )?;
+    // worktrees of earlier runs whose temp dir is gone
+    run_git(mirror, &["worktree", "prune"])?;
+
+    let output = git()
+        .current_dir(mirror)
+        .args(["rev-parse", &format!("refs/heads/{branch}")])
+        .stdin(Stdio::null())
+        .output()
+        .context("running git rev-parse")?;
+    if !output.status.success() {
+        anyhow::bail!("branch {branch} not found in the mirror of {repo}");
+    }
+    let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    progress!("🌱checking out {commit} -> {dest:?}");
+    // git runs in the mirror, so `dest` must not be relative
+    let dest = std::path::absolute(dest).context("resolving the checkout dir")?;
+    let dest = dest.to_string_lossy();
+    let mut args = vec!["worktree", "add", "--detach"];
+    if options.sparse_paths.is_some() {
+        args.push("--no-checkout");
+    }
+    args.extend([dest.as_ref(), commit.as_str()]);
+    run_git(mirror, &args)?;
+    Ok(commit)
+}
+
+/// Hash and author date (RFC 3339) of `HEAD`.
+fn head_commit(checkout: &Path) -> anyhow::Result<(String, String)> {
+    let output = git()
+        .current_dir(checkout)
         .args(["rev-parse", "HEAD"])
         .stdin(Stdio::null())
         .output()
@@ -33,8 +307,8 @@ pub fn shallow_clone_to(repo: &str, branch: &str, dest: &Path) -> anyhow::Result
     let commit_hash = String::from_utf8_lossy(&output.stdout).trim().to_string();
 
     // Capture the author date/time in strict ISO 8601 (RFC 3339) format
-    let output_time = Command::new("git")
-        .current_dir(dest)
+    let output_time = git()
+        .current_dir(checkout)
         .args(["show", "-s", "--format=%aI", "HEAD"]) // author date, ISO-8601 strict
         .stdin(Stdio::null())
         .output()
@@ -45,11 +319,436 @@ pub fn shallow_clone_to(repo: &str, branch: &str, dest: &Path) -> anyhow::Result
     let commit_time_iso = String::from_utf8_lossy(&output_time.stdout)
         .trim()
         .to_string();
+    Ok((commit_hash, commit_time_iso))
+}
 
-    // Remove .git
-    let git_dir = dest.join(".git");
-    if git_dir.exists() {
-        std::fs::remove_dir_all(&git_dir).context("removing .git")?;
+/// A local directory that is (inside) a git checkout.
+#[derive(Debug, Clone)]
+pub struct LocalCheckout {
+    pub commit: ClonedCommit,
+    /// `refs/heads/<branch>`, or `HEAD` when detached.
+    pub resolved_ref: String,
+    /// Uncommitted or untracked files, what is copied is not exactly `commit`.
+    pub modified: bool,
+}
+
+/// Read the commit, branch and submodules of `dir` without changing anything. `None` if `dir`
+/// is not in a git checkout (or git is not installed).
+pub fn read_checkout(dir: &Path) -> anyhow::Result<Option<LocalCheckout>> {
+    let is_checkout = git()
+        .current_dir(dir)
+        .args(["rev-parse", "--verify", "-q", "HEAD"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !is_checkout {
+        return Ok(None);
+    }
+
+    let (commit, committed_at) = head_commit(dir)?;
+    let output = git()
+        .current_dir(dir)
+        .args(["symbolic-ref", "-q", "HEAD"])
+        .stdin(Stdio::null())
+        .output()
+        .context("running git symbolic-ref")?;
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let output = git()
+        .current_dir(dir)
+        // without refreshing (and so rewriting) the index
+        .args(["--no-optional-locks", "status", "--porcelain"])
+        .stdin(Stdio::null())
+        .output()
+        .context("running git status")?;
+    if !output.status.success() {
+        anyhow::bail!("git status failed");
+    }
+
+    Ok(Some(LocalCheckout {
+        commit: ClonedCommit {
+            commit,
+            committed_at,
+            submodules: submodule_commits(dir, true)?,
+        },
+        resolved_ref: if branch.is_empty() {
+            "HEAD".to_string()
+        } else {
+            branch
+        },
+        modified: !output.stdout.is_empty(),
+    }))
+}
+
+fn update_submodules(
+    checkout: &Path,
+    submodules: Submodules,
+) -> anyhow::Result<Vec<SubmoduleCommit>> {
+    progress!("🧩updating submodules ({})", submodules.name());
+    let mut args = vec!["submodule", "update", "--init", "--depth", "1"];
+    if submodules == Submodules::Recursive {
+        args.push("--recursive");
+    }
+    run_git(checkout, &args)?;
+    submodule_commits(checkout, submodules == Submodules::Recursive)
+}
+
+/// The submodules that are checked out and their current commits.
+fn submodule_commits(checkout: &Path, recursive: bool) -> anyhow::Result<Vec<SubmoduleCommit>> {
+    let mut args = vec!["--no-optional-locks", "submodule", "status"];
+    if recursive {
+        args.push("--recursive");
+    }
+    let output = git()
+        .current_dir(checkout)
+        .args(&args)
+        .stdin(Stdio::null())
+        .output()
+        .context("running git submodule status")?;
+    if !output.status.success() {
+        anyhow::bail!("git submodule status failed");
+    }
+    // ` <commit> <path> (<describe>)`, prefixed with `-` if not checked out (and `+` if
+    // another commit than the recorded one is)
+    let mut commits = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let checked_out = !line.starts_with('-');
+        let mut parts = line[1..].split_whitespace();
+        if let (Some(commit), Some(path)) = (parts.next(), parts.next())
+            && checked_out
+        {
+            verbose!("  🧩 {path} @ {commit}");
+            commits.push(SubmoduleCommit {
+                path: path.to_string(),
+                commit: commit.to_string(),
+            });
+        }
+    }
+    Ok(commits)
+}
+
+/// Check out only the directories needed for `paths` (cone mode, files in the repo root are
+/// always included), or everything if a glob in the first component needs it.
+fn sparse_checkout(checkout: &Path, rev: &str, paths: &[String]) -> anyhow::Result<()> {
+    match cone_dirs(paths, |path| object_type(checkout, path))? {
+        Some(dirs) => {
+            verbose!("  sparse checkout: {}", dirs.join(", "));
+            let mut args = vec!["sparse-checkout", "set", "--cone"];
+            args.extend(dirs.iter().map(String::as_str));
+            run_git(checkout, &args)?;
+        }
+        None => verbose!("  sparse checkout: everything, a copy source starts with a glob"),
+    }
+    run_git(checkout, &["checkout", rev])
+}
+
+/// The cone mode directories for `paths`, `None` when one of them has a glob in its first
+/// component (`level*/data`), which no directory below the root covers. `object_type` is
+/// [`object_type`] in the checkout.
+fn cone_dirs(
+    paths: &[String],
+    object_type: impl Fn(&str) -> anyhow::Result<Option<String>>,
+) -> anyhow::Result<Option<Vec<String>>> {
+    let mut dirs = Vec::new();
+    for path in paths {
+        let path = path.trim_matches('/');
+        // up to the first component with a glob, e.g. `data/levels` for `data/levels/*.lvl`
+        let prefix: Vec<&str> = path
+            .split('/')
+            .take_while(|component| !has_glob_meta(component))
+            .collect();
+        let dir = if prefix.len() < path.split('/').count() {
+            if prefix.is_empty() {
+                return Ok(None);
+            }
+            prefix.join("/")
+        } else {
+            match object_type(path)?.as_deref() {
+                // a directory or a submodule
+                Some("tree" | "commit") => path.to_string(),
+                Some(_) => path
+                    .rsplit_once('/')
+                    .map(|(parent, _)| parent.to_string())
+                    .unwrap_or_default(),
+                // reported as not found when copying
+                None => continue,
+            }
+        };
+        if !dir.is_empty() && !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    // `assets/sub` is already part of `assets`
+    let all_dirs = dirs.clone();
+    dirs.retain(|dir| {
+        !all_dirs
+            .iter()
+            .any(|other| dir.starts_with(&format!("{other}/")))
+    });
+    Ok(Some(dirs))
+}
+
+/// `tree`, `blob`, `commit` (submodule) or `None` if `path` does not exist at `HEAD`.
+fn object_type(checkout: &Path, path: &str) -> anyhow::Result<Option<String>> {
+    let output = git()
+        .current_dir(checkout)
+        // the tree entry, `cat-file` would fetch blobs and can not see submodule commits
+        .args(["ls-tree", "HEAD", "--", path])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .context("running git ls-tree")?;
+    // `<mode> <type> <object>\t<path>`
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .nth(1)
+        .map(|kind| kind.to_string()))
+}
+
+/// Token for HTTPS clones and fetches from `host`, see [`use_credential`].
+pub struct GitCredential {
+    pub host: String,
+    pub username: &'static str,
+    pub token: String,
+}
+
+static CREDENTIAL: OnceLock<GitCredential> = OnceLock::new();
+
+/// Answers git's `get` with the user name and token from the environment of the git command.
+const CREDENTIAL_HELPER: &str = r#"!f() { test "$1" = get && echo "username=$BOILER_GIT_USERNAME" && echo "password=$BOILER_GIT_TOKEN"; }; f"#;
+
+/// Let every git command authenticate to `credential.host` with its token.
+pub fn use_credential(credential: GitCredential) {
+    let _ = CREDENTIAL.set(credential);
+}
+
+/// `git`, with a credential helper for the host of [`use_credential`]. The helper reads the
+/// token from the environment, so it is neither in a URL nor on the command line. Submodules
+/// and Git LFS ask the same helper. It is the only helper for the host: git hands working
+/// credentials to every helper to `store`, which would keep the token in the keychain or
+/// `~/.git-credentials`.
+fn git() -> Command {
+    let mut command = Command::new("git");
+    if let Some(credential) = CREDENTIAL.get() {
+        let key = format!("credential.https://{}.helper", credential.host);
+        command
+            // an empty helper clears the ones configured before it
+            .arg("-c")
+            .arg(format!("{key}="))
+            .arg("-c")
+            .arg(format!("{key}={CREDENTIAL_HELPER}"))
+            .env("BOILER_GIT_USERNAME", credential.username)
+            .env("BOILER_GIT_TOKEN", &credential.token)
+            // a rejected token fails instead of asking for a password
+            .env("GIT_TERMINAL_PROMPT", "0");
+    }
+    command
+}
+
+fn run_git(checkout: &Path, args: &[&str]) -> anyhow::Result<()> {
+    let status = git()
+        .current_dir(checkout)
+        .args(args)
+        // LFS objects are pulled after the checkout, if wanted
+        .env("GIT_LFS_SKIP_SMUDGE", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("running git {}", args[0]))?;
+    if !status.success() {
+        anyhow::bail!("git {} failed", args.join(" "));
+    }
+    Ok(())
+}
+
+/// Any `.gitattributes` in the checkout routes files through the LFS filter.
+fn uses_lfs(checkout: &Path) -> bool {
+    WalkDir::new(checkout)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name() == ".gitattributes")
+        .any(|entry| {
+            fs::read_to_string(entry.path())
+                .is_ok_and(|attributes| attributes.contains("filter=lfs"))
+        })
+}
+
+fn lfs_pull(checkout: &Path, lfs: &LfsFetch) -> anyhow::Result<()> {
+    let installed = git()
+        .args(["lfs", "version"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !installed {
+        anyhow::bail!(
+            "the content repo uses Git LFS but git-lfs is not installed (or set `lfs off` in content)"
+        );
+    }
+
+    let mut command = git();
+    command.current_dir(checkout).args(["lfs", "pull"]);
+    match lfs {
+        LfsFetch::Paths(paths) => {
+            progress!("📦pulling Git LFS objects below the copy sources");
+            verbose!("  include: {}", paths.join(", "));
+            command.arg(format!("--include={}", paths.join(",")));
+        }
+        _ => progress!("📦pulling Git LFS objects"),
+    }
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()
+        .context("running git lfs pull")?;
+    if !status.success() {
+        anyhow::bail!("git lfs pull failed");
+    }
+    Ok(())
+}
+
+/// Start of every Git LFS pointer file.
+const LFS_POINTER_PREFIX: &[u8] = b"version https://git-lfs.github.com/spec/v1";
+
+/// Pointer files are small text files, the real objects usually are not.
+const LFS_POINTER_MAX_SIZE: u64 = 1024;
+
+/// Files below `dir` that are Git LFS pointers instead of their contents.
+pub fn find_lfs_pointers(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut pointers = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() || entry.metadata()?.len() > LFS_POINTER_MAX_SIZE {
+            continue;
+        }
+        let mut head = [0u8; LFS_POINTER_PREFIX.len()];
+        let read = fs::File::open(entry.path())?.read(&mut head)?;
+        if head[..read] == *LFS_POINTER_PREFIX {
+            pointers.push(entry.path().strip_prefix(dir).unwrap().to_path_buf());
+        }
+    }
+    Ok(pointers)
+}
+
+/// Resolve `branch` on the remote without cloning (`git ls-remote`).
+pub fn remote_head(repo: &str, branch: &str) -> anyhow::Result<String> {
+    let output = git()
+        .args(["ls-remote", repo, &format!("refs/heads/{branch}")])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .context("running git ls-remote")?;
+    if !output.status.success() {
+        anyhow::bail!("git ls-remote failed for {repo}");
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let Some(commit_hash) = stdout.split_whitespace().next() else {
+        anyhow::bail!("branch {branch} not found in {repo}");
+    };
+    Ok(commit_hash.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    /// `tree` for paths without an extension, else `blob`.
+    fn fake_object_type(path: &str) -> anyhow::Result<Option<String>> {
+        let kind = if path.contains('.') { "blob" } else { "tree" };
+        Ok(Some(kind.to_string()))
+    }
+
+    #[test]
+    fn cone_dirs_of_dirs_files_and_globs() {
+        let dirs = cone_dirs(
+            &paths(&[
+                "assets/",
+                "assets/sub",
+                "config/game.ini",
+                "data/levels/*.lvl",
+            ]),
+            fake_object_type,
+        )
+        .unwrap();
+        assert_eq!(dirs.unwrap(), ["assets", "config", "data/levels"]);
+    }
+
+    #[test]
+    fn cone_dirs_skip_root_files() {
+        let dirs = cone_dirs(&paths(&["game.ini"]), fake_object_type).unwrap();
+        assert!(dirs.unwrap().is_empty());
+    }
+
+    #[test]
+    fn top_level_glob_needs_everything() {
+        let dirs = cone_dirs(&paths(&["assets", "level*/data"]), fake_object_type).unwrap();
+        assert!(dirs.is_none());
+    }
+
+    fn run(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .current_dir(dir)
+            .args([
+                "-c",
+                "user.name=boiler",
+                "-c",
+                "user.email=boiler@example.com",
+            ])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    }
+
+    /// A repo with `files` committed, and a clone of it that has nothing checked out yet.
+    fn unchecked_clone(files: &[&str]) -> (tempfile::TempDir, PathBuf) {
+        let temp = tempfile::tempdir().unwrap();
+        let repo = temp.path().join("repo");
+        for file in files {
+            let path = repo.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        }
+        run(&repo, &["init", "-q"]);
+        run(&repo, &["add", "."]);
+        run(&repo, &["commit", "-q", "-m", "files"]);
+        run(
+            temp.path(),
+            &["clone", "-q", "--no-checkout", "repo", "checkout"],
+        );
+        let checkout = temp.path().join("checkout");
+        (temp, checkout)
+    }
+
+    #[test]
+    fn sparse_checkout_of_a_top_level_glob() {
+        let (_temp, checkout) =
+            unchecked_clone(&["level1/data/a.bin", "level2/data/b.bin", "root.txt"]);
+
+        sparse_checkout(&checkout, "HEAD", &paths(&["level*/data"])).unwrap();
+
+        assert!(checkout.join("level1/data/a.bin").is_file());
+        assert!(checkout.join("level2/data/b.bin").is_file());
+    }
+
+    #[test]
+    fn sparse_checkout_of_a_nested_glob() {
+        let (_temp, checkout) = unchecked_clone(&["data/levels/a.lvl", "other/c.bin", "root.txt"]);
+
+        sparse_checkout(&checkout, "HEAD", &paths(&["data/levels/*.lvl"])).unwrap();
+
+        assert!(checkout.join("data/levels/a.lvl").is_file());
+        assert!(checkout.join("root.txt").is_file());
+        assert!(!checkout.join("other").exists());
     }
-    Ok((commit_hash, commit_time_iso))
 }
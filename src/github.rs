@@ -11,7 +11,9 @@ impl FromStr for GitHubShortName {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let segments: Vec<_> = s.split("/").collect();
-        assert_eq!(segments.len(), 2, "should be two");
+        if segments.len() != 2 || segments.iter().any(|segment| segment.is_empty()) {
+            return Err(());
+        }
 
         Ok(Self {
             org: segments[0].to_string(),
@@ -23,10 +25,3 @@ impl FromStr for GitHubShortName {
 pub fn github_repo_url(repo: &GitHubShortName) -> String {
     format!("https://github.com/{}/{}.git", repo.org, repo.name)
 }
-
-pub fn github_download_url(repo: &GitHubShortName, version: &str, package_name: &str) -> String {
-    format!(
-        "https://github.com/{}/{}/releases/download/v{version}/{}",
-        repo.org, repo.name, package_name
-    )
-}
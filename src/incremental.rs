@@ -0,0 +1,326 @@
+use crate::fsutil::{same_contents, stage_file};
+use crate::yini::{BoilerYini, SizeBudget};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+use walkdir::WalkDir;
+
+/// Lives in the build dir (outside every depot) and remembers where each staged target came from.
+pub const STATE_FILE: &str = ".boiler-state.json";
+
+/// Where a staged target came from. A target is only skipped when the identity is equal and
+/// the `revision` is known.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceIdentity {
+    /// Content repo URL or release asset URL.
+    pub source: String,
+    /// Content commit or asset ETag, `None` if it could not be resolved.
+    pub revision: Option<String>,
+    /// Hash of the copy mappings (content) or of the steam redist listing (binaries), and of
+    /// the settings that change what is staged or whether it passes.
+    pub mapping_hash: String,
+}
+
+impl SourceIdentity {
+    pub fn is_unchanged(&self, previous: Option<&SourceIdentity>) -> bool {
+        self.revision.is_some() && previous == Some(self)
+    }
+}
+
+/// Staging dir (e.g. `binaries/linux`) -> identity.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildState {
+    pub targets: BTreeMap<String, SourceIdentity>,
+}
+
+pub fn load_state(build_dir: &Path) -> BuildState {
+    let path = build_dir.join(STATE_FILE);
+    let Ok(json) = fs::read_to_string(&path) else {
+        return BuildState::default();
+    };
+    serde_json::from_str(&json).unwrap_or_else(|err| {
+        warn!(?path, %err, "ignoring unreadable build state");
+        BuildState::default()
+    })
+}
+
+pub fn save_state(build_dir: &Path, state: &BuildState) -> anyhow::Result<()> {
+    let path = build_dir.join(STATE_FILE);
+    fs::write(&path, serde_json::to_string_pretty(state)?)
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Hash of everything in the content block that changes what is staged from a commit, and of
+/// the settings of [`hash_target_settings`].
+pub fn hash_copy_mappings(ini: &BoilerYini) -> String {
+    let content = &ini.content;
+    let mut hasher = Sha256::new();
+    for mapping in &content.copy {
+        hasher.update(mapping.from.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(mapping.to.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(mapping.mode.name().as_bytes());
+        hasher.update([0]);
+        for pattern in &mapping.exclude {
+            hasher.update(pattern.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.update([1]);
+    }
+    for pattern in content.all_excludes() {
+        hasher.update(pattern.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update(content.lfs.name().as_bytes());
+    hasher.update(content.submodules.name().as_bytes());
+    hasher.update([0]);
+    hash_target_settings(&mut hasher, ini, content.budget);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Hash of the steam redist listing (from [`hash_dir_listing`]) of a binaries target with
+/// size budget `budget`, and of the settings of [`hash_target_settings`].
+pub fn hash_binaries_mapping(ini: &BoilerYini, budget: SizeBudget, redist_listing: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(redist_listing.as_bytes());
+    hasher.update([0]);
+    hash_target_settings(&mut hasher, ini, budget);
+    format!("{:x}", hasher.finalize())
+}
+
+/// `buildinfo_format`, `on_collision` and the target's `max_size` and `max_file_size`: a
+/// skipped target would not get the new build info files, or not be checked against them.
+fn hash_target_settings(hasher: &mut Sha256, ini: &BoilerYini, budget: SizeBudget) {
+    let limit = |limit: Option<u64>| limit.map_or("none".to_string(), |limit| limit.to_string());
+    for setting in [
+        ini.buildinfo_format.name().to_string(),
+        ini.on_collision.name().to_string(),
+        limit(budget.max_size),
+        limit(budget.max_file_size),
+    ] {
+        hasher.update(setting.as_bytes());
+        hasher.update([0]);
+    }
+}
+
+/// Hash of relative paths, sizes and modification times below `dir`, outside of `.git`.
+pub fn hash_dir_listing(dir: &Path) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut entries: Vec<_> = WalkDir::new(dir)
+        .into_iter()
+        // git writes there on its own (fetches, index refreshes), and it is never copied
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("listing {}", dir.display()))?;
+    entries.sort_by(|a, b| a.path().cmp(b.path()));
+    for entry in entries {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let rel = entry.path().strip_prefix(dir).unwrap();
+        hasher.update(rel.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(metadata.len().to_le_bytes());
+        hasher.update(modified.to_le_bytes());
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[derive(Debug, Default)]
+pub struct SyncStats {
+    pub added: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub unchanged: usize,
+}
+
+/// Make `dest_dir` an exact copy of `src_dir`, only writing files that were added or changed
+/// and deleting files that are gone.
+pub fn sync_dir(src_dir: &Path, dest_dir: &Path) -> anyhow::Result<SyncStats> {
+    let mut stats = SyncStats::default();
+    fs::create_dir_all(dest_dir)?;
+
+    let mut wanted = BTreeSet::new();
+    for entry in WalkDir::new(src_dir) {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(src_dir).unwrap().to_path_buf();
+        let out_path = dest_dir.join(&rel);
+        wanted.insert(rel);
+
+        if out_path.is_file() {
+            if same_contents(entry.path(), &out_path)? {
+                stats.unchanged += 1;
+                continue;
+            }
+            stats.updated += 1;
+        } else {
+            stats.added += 1;
+        }
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        stage_file(entry.path(), &out_path)?;
+    }
+
+    // contents_first, so emptied directories can be removed after their files
+    for entry in WalkDir::new(dest_dir).contents_first(true) {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(dest_dir).unwrap();
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+        if entry.file_type().is_dir() {
+            if fs::read_dir(entry.path())?.next().is_none() {
+                fs::remove_dir(entry.path())?;
+            }
+        } else if !wanted.contains(rel) {
+            fs::remove_file(entry.path())
+                .with_context(|| format!("removing {}", entry.path().display()))?;
+            stats.deleted += 1;
+        }
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buildinfo::BuildInfoFormat;
+    use crate::fsutil::CollisionPolicy;
+
+    fn identity(revision: Option<&str>, mapping_hash: &str) -> SourceIdentity {
+        SourceIdentity {
+            source: "https://github.com/org/game.git".to_string(),
+            revision: revision.map(str::to_string),
+            mapping_hash: mapping_hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn unchanged_needs_an_equal_known_identity() {
+        let current = identity(Some("abc"), "hash");
+        assert!(current.is_unchanged(Some(&identity(Some("abc"), "hash"))));
+        assert!(!current.is_unchanged(None));
+        assert!(!current.is_unchanged(Some(&identity(Some("def"), "hash"))));
+        assert!(!current.is_unchanged(Some(&identity(Some("abc"), "other"))));
+        // an unknown revision is never unchanged
+        let unknown = identity(None, "hash");
+        assert!(!unknown.is_unchanged(Some(&identity(None, "hash"))));
+    }
+
+    /// The content and binaries hashes of `ini`.
+    fn hashes(ini: &BoilerYini) -> (String, String) {
+        (
+            hash_copy_mappings(ini),
+            hash_binaries_mapping(ini, ini.binaries.linux.budget, "listing"),
+        )
+    }
+
+    #[test]
+    fn settings_change_the_hashes() {
+        let ini = BoilerYini::default();
+        let base = hashes(&ini);
+        assert_eq!(base, hashes(&BoilerYini::default()));
+
+        let changes: [fn(&mut BoilerYini); 4] = [
+            |ini| ini.buildinfo_format = BuildInfoFormat::Json,
+            |ini| ini.on_collision = CollisionPolicy::Warn,
+            |ini| {
+                ini.content.budget.max_size = Some(1000);
+                ini.binaries.linux.budget.max_size = Some(1000);
+            },
+            |ini| {
+                ini.content.budget.max_file_size = Some(1000);
+                ini.binaries.linux.budget.max_file_size = Some(1000);
+            },
+        ];
+        for (index, change) in changes.iter().enumerate() {
+            let mut changed = BoilerYini::default();
+            change(&mut changed);
+            let (content, binaries) = hashes(&changed);
+            assert_ne!(content, base.0, "change {index}");
+            assert_ne!(binaries, base.1, "change {index}");
+        }
+    }
+
+    #[test]
+    fn content_excludes_change_the_hash() {
+        let mut ini = BoilerYini::default();
+        let base = hash_copy_mappings(&ini);
+        ini.content.exclude.push("*.psd".to_string());
+        assert_ne!(hash_copy_mappings(&ini), base);
+    }
+
+    #[test]
+    fn redist_listing_changes_the_binaries_hash() {
+        let ini = BoilerYini::default();
+        let budget = SizeBudget::default();
+        assert_ne!(
+            hash_binaries_mapping(&ini, budget, "a"),
+            hash_binaries_mapping(&ini, budget, "b")
+        );
+    }
+
+    fn write(root: &Path, file: &str, contents: &str) {
+        let path = root.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn sync_dir_adds_updates_and_deletes() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        write(src.path(), "same.txt", "same");
+        write(src.path(), "dir/changed.txt", "new");
+        write(src.path(), "new/added.txt", "added");
+        write(dest.path(), "same.txt", "same");
+        write(dest.path(), "dir/changed.txt", "old");
+        write(dest.path(), "gone/deleted.txt", "deleted");
+
+        let stats = sync_dir(src.path(), dest.path()).unwrap();
+
+        assert_eq!(
+            (stats.added, stats.updated, stats.deleted, stats.unchanged),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(
+            fs::read_to_string(dest.path().join("dir/changed.txt")).unwrap(),
+            "new"
+        );
+        assert!(dest.path().join("new/added.txt").is_file());
+        // emptied directories go too
+        assert!(!dest.path().join("gone").exists());
+    }
+
+    #[test]
+    fn sync_dir_twice_changes_nothing() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        write(src.path(), "a/b.txt", "b");
+        sync_dir(src.path(), dest.path()).unwrap();
+
+        let stats = sync_dir(src.path(), dest.path()).unwrap();
+
+        assert_eq!(
+            (stats.added, stats.updated, stats.deleted, stats.unchanged),
+            (0, 0, 0, 1)
+        );
+    }
+}
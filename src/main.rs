@@ -1,22 +1,46 @@
 mod args;
+mod branch;
+mod build_result;
+mod buildinfo;
 mod download;
 mod extract;
 mod fetch;
 mod fsutil;
 mod git;
 mod github;
+mod incremental;
+mod manifest;
+mod output;
+mod pipeline;
+mod plan;
+mod portability;
+mod provider;
+mod safety;
+mod steamcmd;
+mod token;
 mod vdf;
 mod yini;
 
-use crate::args::{parse, Target};
-use crate::fetch::extract_to_target;
-use crate::fsutil::{CopyMapping, copy_dir_recursive, copy_mappings, clean_dir};
-use crate::git::shallow_clone_to;
-use crate::github::{github_download_url, github_repo_url};
-use crate::yini::parse_yini;
+use crate::args::{
+    BuildArgs, CleanArgs, Command, ConfigArgs, ManifestCommand, ManifestDiffArgs, StageArgs,
+    UploadArgs, VdfArgs, parse,
+};
+use crate::branch::check_live_branch;
+use crate::build_result::{BuildResult, find_depot_manifests, write_build_result};
+use crate::git::GitCredential;
+use crate::manifest::diff_manifests;
+use crate::output::{Event, ReportInputs, emit, progress, warning, write_report};
+use crate::pipeline::{ALL_TARGETS, BINARY_TARGETS, clean, resolve_targets, stage, write_vdfs};
+use crate::plan::{print_clean_plan, print_stage_plan};
+use crate::provider::Provider;
+use crate::safety::DeleteGuard;
+use crate::steamcmd::run_app_build;
+use crate::token::{https_host, resolve_token};
+use crate::yini::{BinariesSource, BoilerYini, ContentSource, parse_yini, validate_yini};
 use anyhow::{Context, Result, anyhow};
 use chrono::Utc;
 use std::fs;
+use std::time::SystemTime;
 use tracing_subscriber::EnvFilter;
 
 type DepotId = u64;
@@ -30,256 +54,413 @@ pub struct Depot {
 fn main() -> Result<()> {
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env()) // respects RUST_LOG
+        .with_writer(std::io::stderr) // keep stdout for progress and --output json
         .init();
 
-    const VERSION: &str = env!("CARGO_PKG_VERSION");
-    println!("🔥boiler {VERSION} - building up steam...");
+    let args = parse();
+    output::init(args.output, args.verbose);
 
-    let mut args = parse();
+    const VERSION: &str = env!("CARGO_PKG_VERSION");
+    progress!("🔥boiler {VERSION} - building up steam...");
+
+    let result = match args.command {
+        Command::Build(build_args) => build(build_args),
+        Command::Stage(stage_args) => stage_only(stage_args),
+        Command::Vdf(vdf_args) => vdf_only(vdf_args),
+        Command::Upload(upload_args) => upload(upload_args),
+        Command::Clean(clean_args) => clean_only(clean_args),
+        Command::Validate(config_args) => validate(config_args),
+        Command::Info(config_args) => info(config_args),
+        Command::Manifest(ManifestCommand::Diff(diff_args)) => manifest_diff(diff_args),
+    };
 
-    if args.github_token.is_none() {
-        if let Ok(tok) = std::env::var("GITHUB_TOKEN") {
-            if !tok.trim().is_empty() {
-                args.github_token = Some(tok);
-            }
-        }
+    if let Err(err) = &result {
+        emit(Event::Failed {
+            error: format!("{err:#}"),
+        });
+        // what returning the error would print, without the secrets
+        eprintln!("Error: {}", output::redact(&format!("{err:?}")));
+        std::process::exit(1);
     }
+    Ok(())
+}
 
-    // Refuse unsafe/public branches
-    if let Some(ref live_branch_value) = args.live_branch {
-        let branch_lower = live_branch_value.to_lowercase();
-        if branch_lower == "default" || branch_lower == "public" {
-            return Err(anyhow!(
-                "refusing to use forbidden Steam branch '{}' (use a non-public branch)",
-                live_branch_value
-            ));
-        }
+fn report_inputs(ini: &BoilerYini, args: &StageArgs, live_branch: Option<&str>) -> ReportInputs {
+    ReportInputs {
+        config: args.config.ini.clone(),
+        app_id: ini.app_id,
+        targets: resolve_targets(&args.targets)
+            .iter()
+            .map(|target| target.name().to_string())
+            .collect(),
+        live_branch: live_branch.map(|branch| branch.to_string()),
+        content_repo: ini.content.source.location(),
+        binaries_repo: if ini.binaries.uses_release() || ini.binaries.uses_actions() {
+            format!("{}/{}", ini.binaries.repo.org, ini.binaries.repo.name)
+        } else {
+            String::new()
+        },
+        binaries_version: ini.binaries.version.clone(),
     }
+}
 
-    let maybe_init = parse_yini(&args.ini);
-
-    let Some(ini) = maybe_init else {
-        return Err(anyhow!("couldn't parse ini {:?}", args.ini));
+/// Token for the binaries release or workflow run, if the yini uses one. Also lets git clone a
+/// content repo on the same host (or on GitHub) with the matching token.
+fn resolve_tokens(ini: &BoilerYini, args: &StageArgs) -> Result<Option<String>> {
+    let binaries = &ini.binaries;
+    let binaries_token = || {
+        resolve_token(
+            binaries.provider,
+            &binaries.base_url,
+            args.token_file.as_deref(),
+            args.github_token.as_deref(),
+            args.token_from_git_credential,
+        )
+    };
+    let uses_provider = binaries.uses_release() || binaries.uses_actions();
+    let release_token = if uses_provider {
+        binaries_token()?
+    } else {
+        None
     };
 
-    println!(
-        "🧯cooldown: cleaning {} and temp_dir {:?}",
-        if args.keep_build_dir { "temp_dir only" } else { "build_dir and temp_dir" },
-        args.temp_dir
-    );
-    if !args.keep_build_dir {
-        if args.build_dir.exists() {
-            fs::remove_dir_all(&args.build_dir).context("removing build dir")?;
+    if let ContentSource::Git { url, .. } = &ini.content.source
+        && let Some(host) = https_host(url)
+    {
+        // on other hosts git only asks its own credential helpers
+        let credential = if https_host(&binaries.base_url).as_ref() == Some(&host) {
+            let token = if uses_provider {
+                release_token.clone()
+            } else {
+                binaries_token()?
+            };
+            Some((binaries.provider, token))
+        } else if host == "github.com" {
+            let token = resolve_token(
+                Provider::GitHub,
+                "https://github.com",
+                None,
+                args.github_token.as_deref(),
+                args.token_from_git_credential,
+            )?;
+            Some((Provider::GitHub, token))
+        } else {
+            None
+        };
+        if let Some((provider, Some(token))) = credential {
+            git::use_credential(GitCredential {
+                host,
+                username: provider.git_username(),
+                token,
+            });
         }
     }
-    if args.temp_dir.exists() {
-        fs::remove_dir_all(&args.temp_dir).context("removing temp dir")?;
-    }
+    Ok(release_token)
+}
 
-    // resolve targets
-    let selected_targets: Vec<Target> = if args.targets.is_empty() {
-        vec![Target::Content, Target::Mac, Target::Linux, Target::Windows]
-    } else {
-        args.targets.clone()
-    };
-    let process_content = selected_targets.contains(&Target::Content);
-    let process_mac = selected_targets.contains(&Target::Mac);
-    let process_linux = selected_targets.contains(&Target::Linux);
-    let process_windows = selected_targets.contains(&Target::Windows);
-
-    // create/clean dirs as needed
-    if process_content {
-        let p = args.build_dir.join("data");
-        if args.keep_build_dir { clean_dir(&p)?; } else { fs::create_dir_all(&p)?; }
-    }
-    if process_mac {
-        let p = args.build_dir.join("binaries/macos");
-        if args.keep_build_dir { clean_dir(&p)?; } else { fs::create_dir_all(&p)?; }
-    }
-    if process_windows {
-        let p = args.build_dir.join("binaries/windows");
-        if args.keep_build_dir { clean_dir(&p)?; } else { fs::create_dir_all(&p)?; }
-    }
-    if process_linux {
-        let p = args.build_dir.join("binaries/linux");
-        if args.keep_build_dir { clean_dir(&p)?; } else { fs::create_dir_all(&p)?; }
+fn build(args: BuildArgs) -> Result<()> {
+    let ini_path = &args.stage.config.ini;
+    let ini = parse_yini(ini_path)?;
+    let release_token = resolve_tokens(&ini, &args.stage)?;
+
+    if let Some(ref live_branch_value) = args.branch.live_branch {
+        check_live_branch(
+            live_branch_value,
+            &ini,
+            args.branch.yes || args.stage.dry_run,
+        )?;
     }
 
-    let temp_shared_root = args.temp_dir.join("data");
-    if process_content {
-        fs::create_dir_all(&temp_shared_root)?;
+    if args.stage.dry_run {
+        return print_stage_plan(
+            &ini,
+            &args.stage,
+            release_token.as_deref(),
+            Some(args.branch.live_branch.as_deref()),
+        );
     }
 
-    // Start downloads and building
-    let mut content_commit_hash: Option<String> = None;
-    let mut content_commit_time_iso: Option<String> = None;
-    if process_content {
-        println!("🦄fetching your lovely game content...");
-        let repo = github_repo_url(&ini.content.repo);
-        let (hash, time_iso) = shallow_clone_to(&repo, "main", &temp_shared_root)?;
-        content_commit_hash = Some(hash);
-        content_commit_time_iso = Some(time_iso);
-
-        println!("🍬grabbing the goodies...");
-
-        let mut mappings = Vec::new();
-        for (source, target_sub_dir) in ini.content.copy {
-            mappings.push(CopyMapping {
-                from: source.parse()?,
-                to: target_sub_dir,
-            })
-        }
+    stage(&ini, &args.stage, release_token.as_deref())?;
 
-        copy_mappings(&temp_shared_root, &args.build_dir, &mappings)?;
-    }
+    let build_dir = &args.stage.dirs.build_dir;
+    let app_build_vdf_file = write_vdfs(
+        &ini,
+        build_dir,
+        args.branch.live_branch.as_deref(),
+        &resolve_targets(&args.stage.targets),
+    )?;
 
-    println!("🛳️finding binaries to ship...");
-    let prefix = github_download_url(
-        &ini.binaries.repo,
-        &ini.binaries.version,
-        &ini.binaries.name,
-    );
+    write_report(
+        &build_dir.join("build_report.json"),
+        &report_inputs(&ini, &args.stage, args.branch.live_branch.as_deref()),
+    )?;
+
+    let borrow = app_build_vdf_file.canonicalize().unwrap();
+    let complete_app_build_vdf_path = borrow.to_str().unwrap();
 
-    let mut macos_target: Option<std::path::PathBuf> = None;
-    let mut windows_target: Option<std::path::PathBuf> = None;
-    let mut linux_target: Option<std::path::PathBuf> = None;
+    progress!("🎉 all steamed up!");
 
-    if process_mac {
-        let mac_arm_url = format!("{prefix}-darwin-arm64.tar.gz");
-        let target = args.build_dir.join("binaries/macos");
-        extract_to_target(&mac_arm_url, args.github_token.as_deref(), &target)?;
-        copy_dir_recursive(&args.steam_redist.join("osx"), &*target)?;
-        macos_target = Some(target);
-    }
+    progress!(
+        r#"
+tip:
 
-    if process_windows {
-        let windows_url = format!("{prefix}-windows-x86_64.zip");
-        let target = args.build_dir.join("binaries/windows");
-        extract_to_target(&windows_url, args.github_token.as_deref(), &target)?;
-        copy_dir_recursive(&args.steam_redist.join("win64"), &target)?;
-        windows_target = Some(target);
-    }
+brew install steamcmd # or install from https://developer.valvesoftware.com/wiki/SteamCMD
+
+verify the files in the build/ directory and then upload using:
+
+boiler upload {ini_path:?} --build-dir {build_dir:?} --steam-login [your_steam_email]
+
+or run steamcmd yourself:
+
+steamcmd +login [your_steam_email] +run_app_build {complete_app_build_vdf_path:?} +quit
+"#
+    );
 
-    if process_linux {
-        let linux_url = format!("{prefix}-linux-x86_64.tar.gz");
-        let target = args.build_dir.join("binaries/linux");
-        extract_to_target(&linux_url, args.github_token.as_deref(), &target)?;
-        copy_dir_recursive(&args.steam_redist.join("linux64"), &target)?;
-        linux_target = Some(target);
+    Ok(())
+}
+
+fn stage_only(args: StageArgs) -> Result<()> {
+    let ini = parse_yini(&args.config.ini)?;
+    let release_token = resolve_tokens(&ini, &args)?;
+
+    if args.dry_run {
+        return print_stage_plan(&ini, &args, release_token.as_deref(), None);
     }
 
-    let vdf_dir = args.build_dir.clone();
+    stage(&ini, &args, release_token.as_deref())?;
 
-    println!("🧱writing those pesky .vdf files...");
-    let depots: Vec<Depot> = vec![
-        Depot { id: ini.content.depot, vdf: "depot_content.vdf".to_string() },
-        Depot { id: ini.binaries.macos.depot, vdf: "depot_macos.vdf".to_string() },
-        Depot { id: ini.binaries.linux.depot, vdf: "depot_linux.vdf".to_string() },
-        Depot { id: ini.binaries.windows.depot, vdf: "depot_windows.vdf".to_string() },
-    ];
+    write_report(
+        &args.dirs.build_dir.join("build_report.json"),
+        &report_inputs(&ini, &args, None),
+    )?;
 
-    let app_build_vdf_file = vdf_dir.join(format!("app_build_{}.vdf", ini.app_id));
-    let root_vdf_contents = vdf::app_build(
-        ini.app_id,
-        "Internal build",
-        args.live_branch.as_deref(),
-        &depots,
+    progress!(
+        "🎉 staged in {:?}, run `boiler vdf` to write the .vdf files",
+        args.dirs.build_dir
     );
-    println!("  ✅ {app_build_vdf_file:?}");
-    fs::write(&app_build_vdf_file, root_vdf_contents)?;
-
-    if process_content {
-        let content_vdf = vdf::depot(ini.content.depot, &args.build_dir.join("data"));
-        let content_vdf_file = vdf_dir.join("depot_content.vdf");
-        println!("  ✅ {content_vdf_file:?}");
-        fs::write(&content_vdf_file, content_vdf)?;
+    Ok(())
+}
+
+fn vdf_only(args: VdfArgs) -> Result<()> {
+    let ini = parse_yini(&args.config.ini)?;
+
+    if let Some(ref live_branch_value) = args.branch.live_branch {
+        check_live_branch(live_branch_value, &ini, args.branch.yes)?;
     }
 
-    if process_mac {
-        let macos_vdf = vdf::depot_with_os_filter(
-            ini.binaries.macos.depot,
-            &args.build_dir.join("binaries/macos"),
-            "macos",
-        );
-        let macos_vdf_file = vdf_dir.join("depot_macos.vdf");
-        println!("  ✅ {macos_vdf_file:?}");
-        fs::write(macos_vdf_file, macos_vdf)?;
+    let targets = if args.targets.is_empty() {
+        ALL_TARGETS
+            .into_iter()
+            .filter(|target| args.build_dir.join(target.staging_dir()).is_dir())
+            .collect()
+    } else {
+        args.targets.clone()
+    };
+    if targets.is_empty() {
+        return Err(anyhow!(
+            "nothing staged in {:?}, run `boiler stage` first",
+            args.build_dir
+        ));
     }
 
-    if process_linux {
-        let linux_vdf = vdf::depot_with_os_filter(
-            ini.binaries.linux.depot,
-            &args.build_dir.join("binaries/linux"),
-            "linux",
-        );
-        let linux_vdf_file = vdf_dir.join("depot_linux.vdf");
-        println!("  ✅ {linux_vdf_file:?}");
-        fs::write(linux_vdf_file, linux_vdf)?;
+    write_vdfs(
+        &ini,
+        &args.build_dir,
+        args.branch.live_branch.as_deref(),
+        &targets,
+    )?;
+
+    progress!("🎉 all steamed up!");
+    Ok(())
+}
+
+fn clean_only(args: CleanArgs) -> Result<()> {
+    let guard = DeleteGuard::new(args.ini.as_deref(), args.dirs.force);
+
+    if args.dry_run {
+        let problems = print_clean_plan(Some(&args.dirs.build_dir), &args.dirs.temp_dir, &guard);
+        if !problems.is_empty() {
+            return Err(anyhow!("dry run found {} problem(s)", problems.len()));
+        }
+        return Ok(());
     }
 
-    if process_windows {
-        let windows_vdf = vdf::depot_with_os_filter(
-            ini.binaries.windows.depot,
-            &args.build_dir.join("binaries/windows"),
-            "windows",
-        );
-        let windows_vdf_file = vdf_dir.join("depot_windows.vdf");
-        println!("  ✅ {windows_vdf_file:?}");
-        fs::write(windows_vdf_file, windows_vdf)?;
+    clean(Some(&args.dirs.build_dir), &args.dirs.temp_dir, &guard)?;
+    progress!("🎉 all clean!");
+    Ok(())
+}
+
+fn validate(args: ConfigArgs) -> Result<()> {
+    let ini = parse_yini(&args.ini)?;
+
+    let problems = validate_yini(&ini);
+    if !problems.is_empty() {
+        for problem in &problems {
+            warning(problem.clone());
+        }
+        return Err(anyhow!("{:?} has {} problem(s)", args.ini, problems.len()));
     }
 
-    let borrow = app_build_vdf_file.canonicalize().unwrap();
-    let complete_app_build_vdf_path = borrow.to_str().unwrap();
+    progress!("✅ {:?} looks good", args.ini);
+    Ok(())
+}
+
+fn info(args: ConfigArgs) -> Result<()> {
+    let ini: BoilerYini = parse_yini(&args.ini)?;
 
-    let now_utc = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
-
-    // Content buildinfo in data/
-    println!("🏗 writing buildinfo files...");
-    if process_content {
-        let content_buildinfo_path = args.build_dir.join("data").join("buildinfo_content.txt");
-        let content_buildinfo = format!(
-            "repo: {}/{}\ncommit: {}\ncommitted_at_utc: {}\nbuilt_at_utc: {}\n",
-            ini.content.repo.org,
-            ini.content.repo.name,
-            content_commit_hash.as_deref().unwrap_or(""),
-            content_commit_time_iso.as_deref().unwrap_or(""),
-            now_utc
+    progress!("🎮 steam app {}", ini.app_id);
+    if !ini.allowed_branches.is_empty() {
+        progress!("  allowed branches: {}", ini.allowed_branches.join(", "));
+    }
+    if !ini.protected_branches.is_empty() {
+        progress!(
+            "  protected branches: {}",
+            ini.protected_branches.join(", ")
         );
-        fs::write(&content_buildinfo_path, content_buildinfo)?;
     }
 
-    // Binaries buildinfo in each selected platform directory
-    let bin_buildinfo = format!(
-        "repo: {}/{}\nversion: {}\nbuilt_at_utc: {}\n",
-        ini.binaries.repo.org, ini.binaries.repo.name, ini.binaries.version, now_utc
-    );
-    if let Some(target) = &macos_target {
-        fs::write(target.join("buildinfo_binaries.txt"), &bin_buildinfo)?;
+    progress!("🦄 content -> depot {}", ini.content.depot);
+    match &ini.content.source {
+        ContentSource::Git { url, .. } => progress!("  repo: {url}"),
+        ContentSource::Path(dir) => progress!("  path: {dir:?}"),
+    }
+    for mapping in &ini.content.copy {
+        progress!(
+            "  copy: {} -> {} ({})",
+            mapping.from.display(),
+            mapping.to.display(),
+            mapping.mode.name()
+        );
+        if !mapping.exclude.is_empty() {
+            progress!("    exclude: {}", mapping.exclude.join(", "));
+        }
     }
-    if let Some(target) = &windows_target {
-        fs::write(target.join("buildinfo_binaries.txt"), &bin_buildinfo)?;
+    if !ini.content.exclude.is_empty() {
+        progress!("  exclude: {}", ini.content.exclude.join(", "));
     }
-    if let Some(target) = &linux_target {
-        fs::write(target.join("buildinfo_binaries.txt"), &bin_buildinfo)?;
+    if !ini.content.default_excludes {
+        progress!("  default excludes: off");
+    }
+    progress!("  lfs: {}", ini.content.lfs.name());
+    progress!("  submodules: {}", ini.content.submodules.name());
+
+    if let Some(actions) = &ini.binaries.actions
+        && ini.binaries.uses_actions()
+    {
+        progress!(
+            "🛳️ binaries {}/{} from the latest successful {} run on {} ({})",
+            ini.binaries.repo.org,
+            ini.binaries.repo.name,
+            actions.workflow,
+            actions.branch,
+            ini.binaries.base_url
+        );
+    } else if ini.binaries.uses_release() {
+        progress!(
+            "🛳️ binaries {}/{} version {} on {} ({})",
+            ini.binaries.repo.org,
+            ini.binaries.repo.name,
+            ini.binaries.version,
+            ini.binaries.provider.name(),
+            ini.binaries.base_url
+        );
+    } else {
+        progress!("🛳️ binaries");
+    }
+    for target in BINARY_TARGETS {
+        let from = match target.binaries_source(&ini).unwrap() {
+            BinariesSource::Release => format!(
+                "release asset {}{}",
+                ini.binaries.name,
+                target.asset_suffix().unwrap()
+            ),
+            BinariesSource::Artifact(name) => format!("workflow artifact {name}"),
+            BinariesSource::Url { url, headers } if !headers.is_empty() => {
+                let names: Vec<&str> = headers.iter().map(|header| header.name.as_str()).collect();
+                format!("{url} (headers: {})", names.join(", "))
+            }
+            BinariesSource::Url { url, .. } => url.clone(),
+            BinariesSource::Path(path) => format!("{path:?}"),
+        };
+        progress!(
+            "  {} -> depot {}: {from}",
+            target.os_name().unwrap(),
+            target.depot(&ini)
+        );
     }
 
-    println!("🎉 all steamed up!");
+    Ok(())
+}
 
-    println!(
-        r#"
-tip:
+fn upload(args: UploadArgs) -> Result<()> {
+    let ini = parse_yini(&args.config.ini)?;
 
-brew install steamcmd # or install from https://developer.valvesoftware.com/wiki/SteamCMD
+    let login = match args.steam_login {
+        Some(login) => login,
+        None => std::env::var("STEAM_LOGIN")
+            .ok()
+            .filter(|login| !login.trim().is_empty())
+            .ok_or_else(|| anyhow!("missing Steam login (use --steam-login or STEAM_LOGIN)"))?,
+    };
 
-verify the files in the build/ directory and then upload using:
+    let app_build_vdf_file = args.build_dir.join(format!("app_build_{}.vdf", ini.app_id));
+    let app_build_vdf_file = app_build_vdf_file
+        .canonicalize()
+        .with_context(|| format!("missing {app_build_vdf_file:?}, run a build first"))?;
 
-steamcmd +login [your_steam_email] +run_app_build {complete_app_build_vdf_path:?} +quit
+    let live_branch = vdf::setlive_branch(&fs::read_to_string(&app_build_vdf_file)?);
+    if let Some(ref live_branch_value) = live_branch {
+        check_live_branch(live_branch_value, &ini, args.yes)?;
+    }
 
-optionally set the branch to go live:
+    let started = SystemTime::now();
+    let outcome = run_app_build(&args.steamcmd, &login, &app_build_vdf_file)?;
 
-steamcmd +login [your_steam_email] +run_app_build {complete_app_build_vdf_path:?} +setlive internal +quit
-"#
+    progress!(
+        "🎉 uploaded AppID {} as BuildID {}",
+        ini.app_id,
+        outcome.build_id
+    );
+    emit(Event::Uploaded {
+        app_id: ini.app_id,
+        build_id: outcome.build_id,
+    });
+
+    let depot_ids: Vec<DepotId> = ALL_TARGETS
+        .iter()
+        .map(|target| target.depot(&ini))
+        .collect();
+    let depots = find_depot_manifests(
+        &outcome.log,
+        &args.build_dir.join("buildoutput"),
+        started,
+        &depot_ids,
     );
+    for (depot, manifest) in &depots {
+        progress!("  📦 depot {depot} -> manifest {manifest}");
+    }
 
+    let result = BuildResult {
+        app_id: ini.app_id,
+        build_id: outcome.build_id,
+        depots: depots
+            .into_iter()
+            .map(|(depot, manifest)| (depot, manifest.to_string()))
+            .collect(),
+        live_branch,
+        uploaded_at_utc: Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    };
+    let build_result_file = args.build_dir.join("build_result.json");
+    write_build_result(&build_result_file, &result)?;
+    progress!("  ✅ {build_result_file:?}");
+
+    Ok(())
+}
+
+fn manifest_diff(args: ManifestDiffArgs) -> Result<()> {
+    let differences = diff_manifests(&args.old, &args.new)?;
+    if differences == 0 {
+        progress!("🎉 no differences");
+    }
     Ok(())
 }
@@ -0,0 +1,369 @@
+use crate::DepotId;
+use crate::output::{Event, emit, progress};
+use crate::pipeline::ALL_TARGETS;
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Lives in the build dir, outside every depot: `build/manifests/<target>.json`.
+pub const MANIFEST_DIR: &str = "manifests";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    /// Relative to the depot root, always with `/`.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    /// Permission bits in octal, e.g. `755`.
+    pub mode: String,
+}
+
+/// Every file that went into one depot.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepotManifest {
+    pub target: String,
+    pub depot: DepotId,
+    pub files: Vec<ManifestFile>,
+}
+
+pub fn manifest_path(build_dir: &Path, target_name: &str) -> PathBuf {
+    build_dir
+        .join(MANIFEST_DIR)
+        .join(format!("{target_name}.json"))
+}
+
+/// Hash every file below `depot_root` and write the manifest for `target_name`.
+pub fn write_manifest(
+    build_dir: &Path,
+    depot_root: &Path,
+    target_name: &str,
+    depot: DepotId,
+) -> anyhow::Result<()> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(depot_root).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        let rel = entry.path().strip_prefix(depot_root).unwrap();
+        files.push(ManifestFile {
+            path: rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            size: metadata.len(),
+            sha256: sha256(entry.path())?,
+            mode: mode(&metadata),
+        });
+    }
+
+    let manifest = DepotManifest {
+        target: target_name.to_string(),
+        depot,
+        files,
+    };
+    let path = manifest_path(build_dir, target_name);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(&path, serde_json::to_string_pretty(&manifest)?)
+        .with_context(|| format!("writing {}", path.display()))?;
+    emit(Event::ManifestWritten {
+        path,
+        files: manifest.files.len(),
+    });
+    Ok(())
+}
+
+fn sha256(path: &Path) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut fs::File::open(path)?, &mut hasher)
+        .with_context(|| format!("hashing {}", path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(unix)]
+fn mode(metadata: &fs::Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+    format!("{:o}", metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn mode(metadata: &fs::Metadata) -> String {
+    if metadata.permissions().readonly() {
+        "444"
+    } else {
+        "644"
+    }
+    .to_string()
+}
+
+fn read_manifest(path: &Path) -> anyhow::Result<DepotManifest> {
+    let json = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// A manifest file, a manifests dir or a build dir -> manifest file name -> path.
+fn manifest_files(path: &Path) -> anyhow::Result<BTreeMap<String, PathBuf>> {
+    if path.is_file() {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        return Ok(BTreeMap::from([(name, path.to_path_buf())]));
+    }
+    let dir = if path.join(MANIFEST_DIR).is_dir() {
+        path.join(MANIFEST_DIR)
+    } else {
+        path.to_path_buf()
+    };
+    let mut files = BTreeMap::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        // a build dir also has `build_report.json` and the like
+        if ALL_TARGETS
+            .iter()
+            .any(|target| name == format!("{}.json", target.name()))
+        {
+            files.insert(name, path);
+        }
+    }
+    if files.is_empty() {
+        return Err(anyhow!("no manifests found in {}", dir.display()));
+    }
+    Ok(files)
+}
+
+/// Print added, removed and changed files between two builds. `old` and `new` are manifest
+/// files, manifests dirs or build dirs. Returns the number of differences.
+pub fn diff_manifests(old: &Path, new: &Path) -> anyhow::Result<usize> {
+    let old_files = manifest_files(old)?;
+    let new_files = manifest_files(new)?;
+
+    // two single files are compared even if their names differ
+    let pairs: Vec<(Option<&PathBuf>, Option<&PathBuf>)> = if old.is_file() && new.is_file() {
+        vec![(old_files.values().next(), new_files.values().next())]
+    } else {
+        let mut names: Vec<&String> = old_files.keys().chain(new_files.keys()).collect();
+        names.sort();
+        names.dedup();
+        names
+            .into_iter()
+            .map(|name| (old_files.get(name), new_files.get(name)))
+            .collect()
+    };
+
+    let mut differences = 0;
+    for (old_path, new_path) in pairs {
+        let old_manifest = old_path.map(|path| read_manifest(path)).transpose()?;
+        let new_manifest = new_path.map(|path| read_manifest(path)).transpose()?;
+        let target = new_manifest
+            .as_ref()
+            .or(old_manifest.as_ref())
+            .map(|manifest| manifest.target.clone())
+            .unwrap_or_default();
+
+        let old_by_path = by_path(old_manifest.as_ref());
+        let new_by_path = by_path(new_manifest.as_ref());
+        let (added, removed, changed) = changed_paths(&old_by_path, &new_by_path);
+
+        progress!(
+            "📋{target}: {} added, {} removed, {} changed",
+            added.len(),
+            removed.len(),
+            changed.len()
+        );
+        for path in &added {
+            progress!("  + {path} ({} bytes)", new_by_path[path.as_str()].size);
+        }
+        for path in &removed {
+            progress!("  - {path}");
+        }
+        for path in &changed {
+            let (old_file, new_file) = (old_by_path[path.as_str()], new_by_path[path.as_str()]);
+            let mut what = Vec::new();
+            if old_file.size != new_file.size {
+                what.push(format!("{} -> {} bytes", old_file.size, new_file.size));
+            } else if old_file.sha256 != new_file.sha256 {
+                what.push("contents".to_string());
+            }
+            if old_file.mode != new_file.mode {
+                what.push(format!("mode {} -> {}", old_file.mode, new_file.mode));
+            }
+            progress!("  ~ {path} ({})", what.join(", "));
+        }
+
+        differences += added.len() + removed.len() + changed.len();
+        emit(Event::ManifestDiffed {
+            target,
+            added,
+            removed,
+            changed,
+        });
+    }
+    Ok(differences)
+}
+
+/// Added, removed and changed paths, each sorted.
+fn changed_paths(
+    old_by_path: &BTreeMap<&str, &ManifestFile>,
+    new_by_path: &BTreeMap<&str, &ManifestFile>,
+) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    for (path, new_file) in new_by_path {
+        match old_by_path.get(path) {
+            None => added.push(path.to_string()),
+            Some(old_file) if old_file != new_file => changed.push(path.to_string()),
+            Some(_) => {}
+        }
+    }
+    for path in old_by_path.keys() {
+        if !new_by_path.contains_key(path) {
+            removed.push(path.to_string());
+        }
+    }
+    (added, removed, changed)
+}
+
+fn by_path(manifest: Option<&DepotManifest>) -> BTreeMap<&str, &ManifestFile> {
+    manifest
+        .map(|manifest| {
+            manifest
+                .files
+                .iter()
+                .map(|file| (file.path.as_str(), file))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `files` (relative path, contents) into a fresh depot and its manifest into
+    /// `build_dir`.
+    fn build(build_dir: &Path, target_name: &str, files: &[(&str, &str)]) -> DepotManifest {
+        let depot_root = tempfile::tempdir().unwrap();
+        for (file, contents) in files {
+            let path = depot_root.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        write_manifest(build_dir, depot_root.path(), target_name, 1234561).unwrap();
+        read_manifest(&manifest_path(build_dir, target_name)).unwrap()
+    }
+
+    #[test]
+    fn added_removed_and_changed_files() {
+        let old_build = tempfile::tempdir().unwrap();
+        let new_build = tempfile::tempdir().unwrap();
+        let old = build(
+            old_build.path(),
+            "content",
+            &[
+                ("data/kept.txt", "same"),
+                ("data/gone.txt", "gone"),
+                ("data/longer.txt", "short"),
+                ("data/edited.txt", "aaaa"),
+            ],
+        );
+        let new = build(
+            new_build.path(),
+            "content",
+            &[
+                ("data/kept.txt", "same"),
+                ("data/longer.txt", "much longer"),
+                ("data/edited.txt", "bbbb"),
+                ("data/new/added.txt", "new"),
+            ],
+        );
+
+        let (added, removed, changed) = changed_paths(&by_path(Some(&old)), &by_path(Some(&new)));
+        assert_eq!(added, ["data/new/added.txt"]);
+        assert_eq!(removed, ["data/gone.txt"]);
+        assert_eq!(changed, ["data/edited.txt", "data/longer.txt"]);
+
+        assert_eq!(
+            diff_manifests(old_build.path(), new_build.path()).unwrap(),
+            4
+        );
+        assert_eq!(
+            diff_manifests(old_build.path(), old_build.path()).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn a_mode_change_is_a_change() {
+        let old = ManifestFile {
+            path: "game".to_string(),
+            size: 4,
+            sha256: "ab".to_string(),
+            mode: "644".to_string(),
+        };
+        let new = ManifestFile {
+            mode: "755".to_string(),
+            ..old.clone()
+        };
+        let (old, new) = (
+            BTreeMap::from([("game", &old)]),
+            BTreeMap::from([("game", &new)]),
+        );
+        assert_eq!(
+            changed_paths(&old, &new),
+            (Vec::new(), Vec::new(), vec!["game".to_string()])
+        );
+    }
+
+    #[test]
+    fn a_missing_target_is_all_added_or_removed() {
+        let old_build = tempfile::tempdir().unwrap();
+        let new_build = tempfile::tempdir().unwrap();
+        build(old_build.path(), "content", &[("a.txt", "a")]);
+        build(new_build.path(), "content", &[("a.txt", "a")]);
+        build(
+            new_build.path(),
+            "linux",
+            &[("game", "elf"), ("lib/x.so", "so")],
+        );
+
+        assert_eq!(
+            diff_manifests(old_build.path(), new_build.path()).unwrap(),
+            2
+        );
+        assert_eq!(
+            diff_manifests(new_build.path(), old_build.path()).unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn manifest_files_of_a_build_dir_skip_other_json() {
+        let build_dir = tempfile::tempdir().unwrap();
+        build(build_dir.path(), "content", &[("a.txt", "a")]);
+        build(build_dir.path(), "windows", &[("game.exe", "exe")]);
+        fs::write(
+            build_dir
+                .path()
+                .join(MANIFEST_DIR)
+                .join("build_report.json"),
+            "{}",
+        )
+        .unwrap();
+
+        let names: Vec<String> = manifest_files(build_dir.path())
+            .unwrap()
+            .into_keys()
+            .collect();
+        assert_eq!(names, ["content.json", "windows.json"]);
+
+        let single = manifest_path(build_dir.path(), "windows");
+        assert_eq!(manifest_files(&single).unwrap().len(), 1);
+        assert!(manifest_files(tempfile::tempdir().unwrap().path()).is_err());
+    }
+}
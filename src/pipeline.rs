@@ -0,0 +1,713 @@
+use crate::args::{StageArgs, Target};
+use crate::buildinfo::{self, BuildInfo, write_buildinfo};
+use crate::download::{probe, resolve_headers};
+use crate::fetch::{extract_nested_archive, extract_to_target, stage_local};
+use crate::fsutil::{check_size_budget, copy_dir_recursive, copy_mappings, init_staging};
+use crate::git::{
+    LocalCheckout, find_lfs_pointers, mirror_dir, read_checkout, remote_head, shallow_clone_to,
+};
+use crate::incremental::{
+    BuildState, SourceIdentity, hash_binaries_mapping, hash_copy_mappings, hash_dir_listing,
+    load_state, save_state, sync_dir,
+};
+use crate::manifest::write_manifest;
+use crate::output::{Event, emit, progress};
+use crate::portability::check_depot_paths;
+use crate::provider::Release;
+use crate::safety::{DeleteGuard, create_marked_dir};
+use crate::vdf;
+use crate::yini::{BinariesSource, BoilerYini, ContentSource, SizeBudget};
+use crate::{Depot, DepotId};
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+pub const ALL_TARGETS: [Target; 4] = [Target::Content, Target::Mac, Target::Linux, Target::Windows];
+
+pub const BINARY_TARGETS: [Target; 3] = [Target::Mac, Target::Linux, Target::Windows];
+
+impl Target {
+    /// Name used on the command line and in reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Content => "content",
+            Target::Mac => "mac",
+            Target::Linux => "linux",
+            Target::Windows => "windows",
+        }
+    }
+
+    /// Where the depot is staged, relative to the build dir.
+    pub fn staging_dir(self) -> &'static str {
+        match self {
+            Target::Content => "data",
+            Target::Mac => "binaries/macos",
+            Target::Linux => "binaries/linux",
+            Target::Windows => "binaries/windows",
+        }
+    }
+
+    pub fn depot_vdf(self) -> &'static str {
+        match self {
+            Target::Content => "depot_content.vdf",
+            Target::Mac => "depot_macos.vdf",
+            Target::Linux => "depot_linux.vdf",
+            Target::Windows => "depot_windows.vdf",
+        }
+    }
+
+    pub fn size_budget(self, ini: &BoilerYini) -> SizeBudget {
+        match self {
+            Target::Content => ini.content.budget,
+            Target::Mac => ini.binaries.macos.budget,
+            Target::Linux => ini.binaries.linux.budget,
+            Target::Windows => ini.binaries.windows.budget,
+        }
+    }
+
+    pub fn depot(self, ini: &BoilerYini) -> DepotId {
+        match self {
+            Target::Content => ini.content.depot,
+            Target::Mac => ini.binaries.macos.depot,
+            Target::Linux => ini.binaries.linux.depot,
+            Target::Windows => ini.binaries.windows.depot,
+        }
+    }
+
+    /// Steam `oslist` filter, binaries only.
+    pub fn os_name(self) -> Option<&'static str> {
+        match self {
+            Target::Content => None,
+            Target::Mac => Some("macos"),
+            Target::Linux => Some("linux"),
+            Target::Windows => Some("windows"),
+        }
+    }
+
+    /// Suffix appended to the release name to get the asset file name.
+    pub fn asset_suffix(self) -> Option<&'static str> {
+        match self {
+            Target::Content => None,
+            Target::Mac => Some("-darwin-arm64.tar.gz"),
+            Target::Linux => Some("-linux-x86_64.tar.gz"),
+            Target::Windows => Some("-windows-x86_64.zip"),
+        }
+    }
+
+    /// `url`, `path` or the release, binaries only.
+    pub fn binaries_source(self, ini: &BoilerYini) -> Option<&BinariesSource> {
+        match self {
+            Target::Content => None,
+            Target::Mac => Some(&ini.binaries.macos.source),
+            Target::Linux => Some(&ini.binaries.linux.source),
+            Target::Windows => Some(&ini.binaries.windows.source),
+        }
+    }
+
+    /// Subdirectory of `--steam-redist` copied next to the binaries.
+    pub fn steam_redist_dir(self) -> Option<&'static str> {
+        match self {
+            Target::Content => None,
+            Target::Mac => Some("osx"),
+            Target::Linux => Some("linux64"),
+            Target::Windows => Some("win64"),
+        }
+    }
+}
+
+/// Where the binaries of a target are fetched from and how.
+pub struct BinariesRequest {
+    /// Download URL, or the local path.
+    pub location: String,
+    /// Auth for the release provider, or the `headers` of a `url`.
+    pub headers: Vec<(String, String)>,
+    /// Name to save the download as, when the URL does not end in it (workflow artifacts).
+    pub file_name: Option<String>,
+}
+
+/// Look up the binaries release, or the workflow run for `source actions`, if one of
+/// `targets` downloads from it.
+pub fn resolve_release(
+    ini: &BoilerYini,
+    targets: &[Target],
+    release_token: Option<&str>,
+) -> Result<Option<Release>> {
+    let uses_provider = targets.iter().any(|target| {
+        matches!(
+            target.binaries_source(ini),
+            Some(BinariesSource::Release | BinariesSource::Artifact(_))
+        )
+    });
+    if !uses_provider {
+        return Ok(None);
+    }
+    let release = match &ini.binaries.actions {
+        Some(actions) => ini.binaries.provider.resolve_workflow_run(
+            &ini.binaries.base_url,
+            &ini.binaries.repo,
+            &actions.workflow,
+            &actions.branch,
+            release_token,
+        )?,
+        None => ini.binaries.provider.resolve_release(
+            &ini.binaries.base_url,
+            &ini.binaries.repo,
+            &format!("v{}", ini.binaries.version),
+            release_token,
+        )?,
+    };
+    Ok(Some(release))
+}
+
+/// Resolve the source of a binaries target: the release asset or workflow artifact (`release`
+/// from [`resolve_release`]), or the `url` with its `headers` read from the environment, or
+/// the `path`.
+pub fn binaries_request(
+    ini: &BoilerYini,
+    target: Target,
+    release: Option<&Release>,
+    release_token: Option<&str>,
+) -> Result<BinariesRequest> {
+    let request = match target.binaries_source(ini).unwrap() {
+        BinariesSource::Release => {
+            let release =
+                release.ok_or_else(|| anyhow!("the binaries release was not resolved"))?;
+            let asset = release.asset(&format!(
+                "{}{}",
+                ini.binaries.name,
+                target.asset_suffix().unwrap()
+            ))?;
+            BinariesRequest {
+                location: asset.url.clone(),
+                headers: release.download_headers(&asset.url, release_token),
+                file_name: None,
+            }
+        }
+        BinariesSource::Artifact(name) => {
+            let release = release.ok_or_else(|| anyhow!("the workflow run was not resolved"))?;
+            let artifact = release.asset(name)?;
+            BinariesRequest {
+                location: artifact.url.clone(),
+                headers: release.download_headers(&artifact.url, release_token),
+                // the download URL ends in `/zip`
+                file_name: Some(format!("{name}.zip")),
+            }
+        }
+        BinariesSource::Url { url, headers } => BinariesRequest {
+            location: url.clone(),
+            headers: resolve_headers(headers)
+                .with_context(|| format!("binaries.{}", target.os_name().unwrap()))?,
+            file_name: None,
+        },
+        BinariesSource::Path(path) => BinariesRequest {
+            location: path.display().to_string(),
+            headers: Vec::new(),
+            file_name: None,
+        },
+    };
+    Ok(request)
+}
+
+/// All targets when none were asked for.
+pub fn resolve_targets(targets: &[Target]) -> Vec<Target> {
+    if targets.is_empty() {
+        ALL_TARGETS.to_vec()
+    } else {
+        targets.to_vec()
+    }
+}
+
+/// Delete `temp_dir`, and `build_dir` unless it is `None`. Both are checked with `guard`
+/// before anything is deleted.
+pub fn clean(build_dir: Option<&Path>, temp_dir: &Path, guard: &DeleteGuard) -> Result<()> {
+    progress!(
+        "🧯cooldown: cleaning {} and temp_dir {:?}",
+        if build_dir.is_none() {
+            "temp_dir only"
+        } else {
+            "build_dir and temp_dir"
+        },
+        temp_dir
+    );
+    if let Some(build_dir) = build_dir {
+        guard.check(build_dir).context("cleaning build dir")?;
+    }
+    guard.check(temp_dir).context("cleaning temp dir")?;
+
+    if let Some(build_dir) = build_dir {
+        guard.remove_dir_all(build_dir)?;
+    }
+    guard.remove_dir_all(temp_dir)?;
+    Ok(())
+}
+
+/// Clean, then fetch content and binaries for the selected targets into the build dir
+/// and write the buildinfo files.
+///
+/// With `--keep-build-dir` the build is incremental: targets whose source identity matches
+/// the build state are skipped, the others are staged in the temp dir and synced into the
+/// build dir file by file.
+pub fn stage(ini: &BoilerYini, args: &StageArgs, release_token: Option<&str>) -> Result<()> {
+    let build_dir = &args.dirs.build_dir;
+    let temp_dir = &args.dirs.temp_dir;
+    let incremental = args.keep_build_dir;
+
+    init_staging(args.staging);
+    check_cache_dir(args)?;
+
+    let guard = DeleteGuard::new(Some(&args.config.ini), args.dirs.force);
+    if incremental {
+        // the staging dirs inside are synced below
+        guard.check(build_dir).context("reusing build dir")?;
+    }
+    clean(
+        (!incremental).then_some(build_dir.as_path()),
+        temp_dir,
+        &guard,
+    )?;
+    create_marked_dir(build_dir)?;
+    create_marked_dir(temp_dir)?;
+
+    let mut state = if incremental {
+        load_state(build_dir)
+    } else {
+        BuildState::default()
+    };
+
+    // fresh builds stage straight into the build dir
+    let stage_root = if incremental {
+        temp_dir.join("stage")
+    } else {
+        build_dir.clone()
+    };
+
+    let selected_targets = resolve_targets(&args.targets);
+    let now_utc = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+
+    // Start downloads and building
+    if selected_targets.contains(&Target::Content) {
+        let source = &ini.content.source;
+        let mut identity = SourceIdentity {
+            source: source.location(),
+            revision: if incremental {
+                content_revision(source)
+            } else {
+                None
+            },
+            mapping_hash: hash_copy_mappings(ini),
+        };
+
+        let started = target_started(Target::Content);
+        if is_unchanged(build_dir, Target::Content, &identity, &state) {
+            progress!("⏭️content unchanged, skipping");
+            target_finished(Target::Content, started, true);
+        } else {
+            fs::create_dir_all(stage_root.join(Target::Content.staging_dir()))?;
+
+            progress!("🦄fetching your lovely game content...");
+            let (content_root, checkout) = match source {
+                ContentSource::Git { url, .. } => {
+                    let temp_shared_root = temp_dir.join("data");
+                    fs::create_dir_all(&temp_shared_root)?;
+                    let mut options = ini.content.clone_options();
+                    options.mirror = args.cache_dir.as_ref().map(|dir| mirror_dir(dir, url));
+                    let cloned = shallow_clone_to(url, "main", &temp_shared_root, &options)?;
+                    let checkout = LocalCheckout {
+                        commit: cloned,
+                        resolved_ref: "refs/heads/main".to_string(),
+                        modified: false,
+                    };
+                    (temp_shared_root, Some(checkout))
+                }
+                ContentSource::Path(dir) => {
+                    if !dir.is_dir() {
+                        return Err(anyhow!("content.path {dir:?} is not a directory"));
+                    }
+                    progress!("📂using the local directory {dir:?}");
+                    let checkout = read_checkout(dir)?;
+                    match &checkout {
+                        Some(checkout) if checkout.modified => {
+                            progress!("  ⚠️ {} with uncommitted changes", checkout.commit.commit)
+                        }
+                        Some(checkout) => progress!("  {}", checkout.commit.commit),
+                        None => progress!("  not a git checkout, no commit is recorded"),
+                    }
+                    (dir.clone(), checkout)
+                }
+            };
+            if let Some(checkout) = &checkout {
+                emit(Event::ContentResolved {
+                    repo: source.location(),
+                    commit: checkout.commit.commit.clone(),
+                });
+            }
+
+            progress!("🍬grabbing the goodies...");
+
+            copy_mappings(
+                &content_root,
+                &stage_root,
+                &ini.content.copy,
+                &ini.content.all_excludes(),
+                ini.on_collision,
+            )?;
+            check_no_lfs_pointers(&stage_root.join(Target::Content.staging_dir()), source)?;
+
+            // Content buildinfo in data/
+            let info = BuildInfo {
+                repo: source.name(),
+                commit: checkout
+                    .as_ref()
+                    .map(|checkout| checkout.commit.commit.clone()),
+                committed_at_utc: checkout
+                    .as_ref()
+                    .map(|checkout| checkout.commit.committed_at.clone()),
+                submodules: checkout
+                    .iter()
+                    .flat_map(|checkout| &checkout.commit.submodules)
+                    .map(|submodule| (submodule.path.clone(), submodule.commit.clone()))
+                    .collect(),
+                modified: checkout.as_ref().is_some_and(|checkout| checkout.modified),
+                run_id: None,
+                version: None,
+                resolved_ref: checkout
+                    .as_ref()
+                    .map(|checkout| checkout.resolved_ref.clone())
+                    .unwrap_or_default(),
+                asset_name: None,
+                asset_sha256: None,
+                built_at_utc: now_utc.clone(),
+                boiler_version: env!("CARGO_PKG_VERSION"),
+                steam_app_id: ini.app_id,
+                steam_depot_id: ini.content.depot,
+            };
+            write_buildinfo(
+                &stage_root.join("data"),
+                &build_dir.join("data"),
+                buildinfo::CONTENT_TXT,
+                &info,
+                ini.buildinfo_format,
+            )?;
+            finish_target(ini, build_dir, &stage_root, Target::Content, incremental)?;
+
+            identity.revision = match (source, checkout) {
+                (ContentSource::Git { .. }, Some(checkout)) => Some(checkout.commit.commit),
+                _ => identity
+                    .revision
+                    .take()
+                    .or_else(|| content_revision(source)),
+            };
+            state
+                .targets
+                .insert(Target::Content.staging_dir().to_string(), identity);
+            save_state(build_dir, &state)?;
+            target_finished(Target::Content, started, false);
+        }
+    }
+
+    progress!("🛳️finding binaries to ship...");
+    let binary_targets: Vec<Target> = BINARY_TARGETS
+        .into_iter()
+        .filter(|target| selected_targets.contains(target))
+        .collect();
+    let release = resolve_release(ini, &binary_targets, release_token)?;
+
+    for target in binary_targets {
+        let source = target.binaries_source(ini).unwrap();
+        let request = binaries_request(ini, target, release.as_ref(), release_token)?;
+        let redist_dir = args.steam_redist.join(target.steam_redist_dir().unwrap());
+        let identity = SourceIdentity {
+            source: request.location.clone(),
+            revision: match source {
+                _ if !incremental => None,
+                BinariesSource::Path(path) => hash_dir_listing(path).ok(),
+                // a new run has new artifacts
+                BinariesSource::Artifact(_) => release
+                    .as_ref()
+                    .and_then(|release| release.run.as_ref())
+                    .map(|run| run.id.to_string()),
+                _ => probe(&request.location, &request.headers)
+                    .ok()
+                    .and_then(|asset| asset.etag),
+            },
+            mapping_hash: hash_binaries_mapping(
+                ini,
+                target.size_budget(ini),
+                &hash_dir_listing(&redist_dir).unwrap_or_default(),
+            ),
+        };
+
+        let started = target_started(target);
+        if is_unchanged(build_dir, target, &identity, &state) {
+            progress!("⏭️{} unchanged, skipping", target.staging_dir());
+            target_finished(target, started, true);
+            continue;
+        }
+
+        let target_dir = stage_root.join(target.staging_dir());
+        fs::create_dir_all(&target_dir)?;
+        let asset = match source {
+            BinariesSource::Path(path) => stage_local(path, &target_dir, ini.on_collision)?,
+            _ => extract_to_target(
+                &request.location,
+                &request.headers,
+                request.file_name.as_deref(),
+                &target_dir,
+            )?,
+        };
+        if matches!(source, BinariesSource::Artifact(_)) {
+            extract_nested_archive(&target_dir)?;
+        }
+        copy_dir_recursive(&redist_dir, &target_dir, ini.on_collision)?;
+
+        // Binaries buildinfo in each selected platform directory
+        let from_provider = release.as_ref().filter(|_| {
+            matches!(
+                source,
+                BinariesSource::Release | BinariesSource::Artifact(_)
+            )
+        });
+        let run = from_provider.and_then(|release| release.run.as_ref());
+        let info = BuildInfo {
+            repo: if from_provider.is_some() {
+                format!("{}/{}", ini.binaries.repo.org, ini.binaries.repo.name)
+            } else {
+                request.location.clone()
+            },
+            commit: run.map(|run| run.head_sha.clone()),
+            committed_at_utc: None,
+            submodules: BTreeMap::new(),
+            modified: false,
+            run_id: run.map(|run| run.id),
+            version: (!ini.binaries.version.is_empty()).then(|| ini.binaries.version.clone()),
+            resolved_ref: from_provider
+                .map(|release| release.resolved_ref.clone())
+                .unwrap_or_default(),
+            asset_name: Some(asset.name),
+            asset_sha256: asset.sha256,
+            built_at_utc: now_utc.clone(),
+            boiler_version: env!("CARGO_PKG_VERSION"),
+            steam_app_id: ini.app_id,
+            steam_depot_id: target.depot(ini),
+        };
+        write_buildinfo(
+            &target_dir,
+            &build_dir.join(target.staging_dir()),
+            buildinfo::BINARIES_TXT,
+            &info,
+            ini.buildinfo_format,
+        )?;
+        finish_target(ini, build_dir, &stage_root, target, incremental)?;
+
+        state
+            .targets
+            .insert(target.staging_dir().to_string(), identity);
+        save_state(build_dir, &state)?;
+        target_finished(target, started, false);
+    }
+
+    Ok(())
+}
+
+fn is_unchanged(
+    build_dir: &Path,
+    target: Target,
+    identity: &SourceIdentity,
+    state: &BuildState,
+) -> bool {
+    build_dir.join(target.staging_dir()).is_dir()
+        && identity.is_unchanged(state.targets.get(target.staging_dir()))
+}
+
+/// Check the size budget, then for incremental builds, sync the target from the stage root
+/// into the build dir. Then write the manifest of the staged depot.
+fn finish_target(
+    ini: &BoilerYini,
+    build_dir: &Path,
+    stage_root: &Path,
+    target: Target,
+    incremental: bool,
+) -> Result<()> {
+    check_size_budget(
+        target.name(),
+        &stage_root.join(target.staging_dir()),
+        &target.size_budget(ini),
+    )?;
+    if incremental {
+        sync_target(build_dir, stage_root, target)?;
+    }
+    write_manifest(
+        build_dir,
+        &build_dir.join(target.staging_dir()),
+        target.name(),
+        target.depot(ini),
+    )
+}
+
+fn sync_target(build_dir: &Path, stage_root: &Path, target: Target) -> Result<()> {
+    let stats = sync_dir(
+        &stage_root.join(target.staging_dir()),
+        &build_dir.join(target.staging_dir()),
+    )?;
+    progress!(
+        "🔁{}: {} added, {} updated, {} deleted, {} unchanged",
+        target.staging_dir(),
+        stats.added,
+        stats.updated,
+        stats.deleted,
+        stats.unchanged
+    );
+    emit(Event::Synced {
+        target: target.name().to_string(),
+        added: stats.added,
+        updated: stats.updated,
+        deleted: stats.deleted,
+        unchanged: stats.unchanged,
+    });
+    Ok(())
+}
+
+/// What the incremental build compares for content: the remote branch tip, or for a local
+/// directory its file listing.
+fn content_revision(source: &ContentSource) -> Option<String> {
+    match source {
+        ContentSource::Git { url, .. } => remote_head(url, "main").ok(),
+        ContentSource::Path(dir) => hash_dir_listing(dir).ok(),
+    }
+}
+
+/// The mirror must survive the clean at the start of every run.
+fn check_cache_dir(args: &StageArgs) -> Result<()> {
+    let Some(cache_dir) = &args.cache_dir else {
+        return Ok(());
+    };
+    let cache_dir = std::path::absolute(cache_dir)?;
+    for dir in [&args.dirs.build_dir, &args.dirs.temp_dir] {
+        if cache_dir.starts_with(std::path::absolute(dir)?) {
+            return Err(anyhow!(
+                "--cache-dir {cache_dir:?} is inside {dir:?}, which is deleted on every build"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Fail if Git LFS pointers were copied instead of the files they point to.
+fn check_no_lfs_pointers(depot_root: &Path, source: &ContentSource) -> Result<()> {
+    let pointers = find_lfs_pointers(depot_root)?;
+    if pointers.is_empty() {
+        return Ok(());
+    }
+    let hint = match source {
+        ContentSource::Git { .. } => "are they outside `lfs copied`, or is `lfs off`?",
+        ContentSource::Path(_) => "run `git lfs pull` in the content directory",
+    };
+    Err(anyhow!(
+        "{} Git LFS pointer file(s) would be uploaded instead of their contents ({hint}):\n  {}",
+        pointers.len(),
+        pointers
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join("\n  ")
+    ))
+}
+
+/// Fail with every staged path that is not valid on Windows or macOS.
+fn check_staged_paths(build_dir: &Path, targets: &[Target]) -> Result<()> {
+    progress!("🔎checking file names...");
+    let mut problems = Vec::new();
+    for target in targets {
+        let depot_root = build_dir.join(target.staging_dir());
+        if !depot_root.is_dir() {
+            continue;
+        }
+        for problem in check_depot_paths(&depot_root, *target)? {
+            problems.push(format!("{}/{problem}", target.staging_dir()));
+        }
+    }
+    if !problems.is_empty() {
+        return Err(anyhow!(
+            "{} path(s) can not be installed by every player:\n  {}",
+            problems.len(),
+            problems.join("\n  ")
+        ));
+    }
+    Ok(())
+}
+
+fn target_started(target: Target) -> Instant {
+    emit(Event::TargetStarted {
+        target: target.name().to_string(),
+    });
+    Instant::now()
+}
+
+fn target_finished(target: Target, started: Instant, skipped: bool) {
+    emit(Event::TargetFinished {
+        target: target.name().to_string(),
+        seconds: started.elapsed().as_secs_f64(),
+        skipped,
+    });
+}
+
+/// Write `app_build_<app_id>.vdf` (listing every depot) and the depot VDFs for `targets`,
+/// after checking that every staged path can be installed on the depot's platforms.
+/// Returns the app_build VDF path.
+pub fn write_vdfs(
+    ini: &BoilerYini,
+    build_dir: &Path,
+    live_branch: Option<&str>,
+    targets: &[Target],
+) -> Result<PathBuf> {
+    let vdf_dir = build_dir.to_path_buf();
+
+    check_staged_paths(build_dir, targets)?;
+
+    progress!("🧱writing those pesky .vdf files...");
+    let depots: Vec<Depot> = ALL_TARGETS
+        .iter()
+        .map(|target| Depot {
+            id: target.depot(ini),
+            vdf: target.depot_vdf().to_string(),
+        })
+        .collect();
+
+    let build_output_dir = vdf_dir.join("buildoutput");
+    fs::create_dir_all(&build_output_dir)?;
+    let app_build_vdf_file = vdf_dir.join(format!("app_build_{}.vdf", ini.app_id));
+    let root_vdf_contents = vdf::app_build(
+        ini.app_id,
+        "Internal build",
+        live_branch,
+        &build_output_dir.canonicalize()?,
+        &depots,
+    );
+    progress!("  ✅ {app_build_vdf_file:?}");
+    fs::write(&app_build_vdf_file, root_vdf_contents)?;
+    emit(Event::VdfWritten {
+        path: app_build_vdf_file.clone(),
+    });
+
+    for target in ALL_TARGETS {
+        if !targets.contains(&target) {
+            continue;
+        }
+        let content_root = build_dir.join(target.staging_dir());
+        let depot_vdf = match target.os_name() {
+            Some(os_name) => vdf::depot_with_os_filter(target.depot(ini), &content_root, os_name),
+            None => vdf::depot(target.depot(ini), &content_root),
+        };
+        let depot_vdf_file = vdf_dir.join(target.depot_vdf());
+        progress!("  ✅ {depot_vdf_file:?}");
+        fs::write(&depot_vdf_file, depot_vdf)?;
+        emit(Event::VdfWritten {
+            path: depot_vdf_file,
+        });
+    }
+
+    Ok(app_build_vdf_file)
+}
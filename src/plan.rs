@@ -0,0 +1,234 @@
+use crate::args::{StageArgs, Target};
+use crate::buildinfo;
+use crate::download::{RemoteAsset, probe};
+use crate::git::{mirror_dir, remote_head};
+use crate::manifest::manifest_path;
+use crate::output::progress;
+use crate::pipeline::{
+    ALL_TARGETS, BINARY_TARGETS, binaries_request, resolve_release, resolve_targets,
+};
+use crate::safety::DeleteGuard;
+use crate::yini::{BinariesSource, BoilerYini, ContentSource};
+use anyhow::anyhow;
+use std::path::Path;
+
+/// Print what `clean` would delete. Returns the directories `guard` refuses to delete.
+pub fn print_clean_plan(
+    build_dir: Option<&Path>,
+    temp_dir: &Path,
+    guard: &DeleteGuard,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    progress!("🧯would delete:");
+    for dir in build_dir.into_iter().chain([temp_dir]) {
+        if let Err(err) = guard.check(dir) {
+            progress!("  ❌ {err}");
+            problems.push(err.to_string());
+        } else if dir.exists() {
+            progress!("  🗑️ {dir:?}");
+        } else {
+            progress!("  · {dir:?} (does not exist)");
+        }
+    }
+    problems
+}
+
+fn excluding(patterns: &[String]) -> String {
+    if patterns.is_empty() {
+        String::new()
+    } else {
+        format!(" excluding {}", patterns.join(", "))
+    }
+}
+
+/// Resolve everything `stage` (and `write_vdfs` if `vdf_live_branch` is `Some`) would do and
+/// print it, without touching the disk. Remote lookups that fail are reported and make the
+/// dry run fail at the end.
+pub fn print_stage_plan(
+    ini: &BoilerYini,
+    args: &StageArgs,
+    release_token: Option<&str>,
+    vdf_live_branch: Option<Option<&str>>,
+) -> anyhow::Result<()> {
+    let build_dir = &args.dirs.build_dir;
+    let temp_dir = &args.dirs.temp_dir;
+    let selected_targets = resolve_targets(&args.targets);
+    let guard = DeleteGuard::new(Some(&args.config.ini), args.dirs.force);
+
+    progress!("🔍dry run, nothing is deleted, downloaded or written");
+
+    let mut problems = print_clean_plan(
+        (!args.keep_build_dir).then_some(build_dir.as_path()),
+        temp_dir,
+        &guard,
+    );
+    if args.keep_build_dir {
+        if let Err(err) = guard.check(build_dir) {
+            progress!("  ❌ {err}");
+            problems.push(err.to_string());
+        }
+        for target in &selected_targets {
+            let dir = build_dir.join(target.staging_dir());
+            if dir.exists() {
+                progress!("  🔁 {dir:?} (synced, or skipped if unchanged)");
+            }
+        }
+    }
+
+    if selected_targets.contains(&Target::Content) {
+        match &ini.content.source {
+            ContentSource::Git { url, .. } => {
+                progress!("🦄would clone:");
+                match remote_head(url, "main") {
+                    Ok(commit) => {
+                        progress!(
+                            "  🌱 {url} @ main ({commit}) -> {:?}",
+                            temp_dir.join("data")
+                        )
+                    }
+                    Err(err) => {
+                        progress!("  ❌ {url} @ main: {err:#}");
+                        problems.push(err.to_string());
+                    }
+                }
+                if let Some(cache_dir) = &args.cache_dir {
+                    let mirror = mirror_dir(cache_dir, url);
+                    if mirror.join("HEAD").is_file() {
+                        progress!("  🪞 via the mirror {mirror:?}, fetching only new objects");
+                    } else {
+                        progress!("  🪞 via a new mirror {mirror:?}");
+                    }
+                }
+            }
+            ContentSource::Path(dir) => {
+                progress!("🦄would use:");
+                if dir.is_dir() {
+                    progress!("  📂 {dir:?}");
+                } else {
+                    let problem = format!("content.path {dir:?} is not a directory");
+                    progress!("  ❌ {problem}");
+                    problems.push(problem);
+                }
+            }
+        }
+
+        progress!("🍬would copy:");
+        for mapping in &ini.content.copy {
+            progress!(
+                "  📄 {} -> {:?} ({}){}",
+                mapping.from.display(),
+                build_dir.join(&mapping.to),
+                mapping.mode.name(),
+                excluding(&mapping.exclude)
+            );
+        }
+        if !ini.content.exclude.is_empty() {
+            progress!("  🚫 everywhere{}", excluding(&ini.content.exclude));
+        }
+    }
+
+    let binary_targets: Vec<Target> = BINARY_TARGETS
+        .into_iter()
+        .filter(|target| selected_targets.contains(target))
+        .collect();
+    if !binary_targets.is_empty() {
+        progress!("🛳️would download:");
+    }
+    let release = match resolve_release(ini, &binary_targets, release_token) {
+        Ok(release) => release,
+        Err(err) => {
+            progress!("  ❌ {err:#}");
+            problems.push(format!("{err:#}"));
+            None
+        }
+    };
+    for target in binary_targets {
+        let target_dir = build_dir.join(target.staging_dir());
+        let source = target.binaries_source(ini).unwrap();
+        if matches!(
+            source,
+            BinariesSource::Release | BinariesSource::Artifact(_)
+        ) && release.is_none()
+        {
+            // already reported
+            continue;
+        }
+        let request = match binaries_request(ini, target, release.as_ref(), release_token) {
+            Ok(request) => request,
+            Err(err) => {
+                progress!("  ❌ {err:#}");
+                problems.push(format!("{err:#}"));
+                continue;
+            }
+        };
+        let url = &request.location;
+        if let BinariesSource::Path(path) = source {
+            if path.exists() {
+                progress!("  📂 {path:?} -> {target_dir:?}");
+            } else {
+                progress!("  ❌ missing {path:?}");
+                problems.push(format!("missing: {}", path.display()));
+            }
+        } else if let BinariesSource::Artifact(name) = source {
+            // listed by the run, and the download URL only answers GET
+            progress!("  📡 artifact {name} {url} -> {target_dir:?}");
+        } else {
+            match probe(url, &request.headers) {
+                Ok(RemoteAsset {
+                    size: Some(size), ..
+                }) => {
+                    progress!("  📡 {url} ({size} bytes) -> {target_dir:?}")
+                }
+                Ok(_) => progress!("  📡 {url} -> {target_dir:?}"),
+                Err(err) => {
+                    progress!("  ❌ {url}: {err:#}");
+                    problems.push(err.to_string());
+                }
+            }
+        }
+
+        let redist = args.steam_redist.join(target.steam_redist_dir().unwrap());
+        if redist.is_dir() {
+            progress!("  📂 {redist:?} -> {target_dir:?}");
+        } else {
+            progress!("  ❌ missing steam redist {redist:?}");
+            problems.push(format!("missing: {}", redist.display()));
+        }
+    }
+
+    progress!("🏗 would write:");
+    for target in &selected_targets {
+        let txt_name = match target {
+            Target::Content => buildinfo::CONTENT_TXT,
+            _ => buildinfo::BINARIES_TXT,
+        };
+        for file_name in ini.buildinfo_format.file_names(txt_name) {
+            progress!(
+                "  ✍️ {:?}",
+                build_dir.join(target.staging_dir()).join(file_name)
+            );
+        }
+        progress!("  ✍️ {:?}", manifest_path(build_dir, target.name()));
+    }
+    if let Some(live_branch) = vdf_live_branch {
+        progress!(
+            "  ✍️ {:?}{}",
+            build_dir.join(format!("app_build_{}.vdf", ini.app_id)),
+            live_branch
+                .map(|branch| format!(" (setlive {branch})"))
+                .unwrap_or_default()
+        );
+        for target in ALL_TARGETS {
+            if selected_targets.contains(&target) {
+                progress!("  ✍️ {:?}", build_dir.join(target.depot_vdf()));
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(anyhow!("dry run found {} problem(s)", problems.len()));
+    }
+
+    progress!("✅ dry run looks good");
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use crate::args::Target;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// `MAX_PATH` on Windows. The install directory comes on top, so a depot path this long can
+/// never be installed.
+const WINDOWS_MAX_PATH: usize = 260;
+
+/// Longest file or directory name on NTFS, APFS and ext4.
+const MAX_NAME_BYTES: usize = 255;
+
+const WINDOWS_RESERVED_CHARS: &[char] = &['<', '>', ':', '"', '\\', '|', '?', '*'];
+
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Which rules apply to a depot: content is installed everywhere, binaries on their own OS.
+fn checks_windows(target: Target) -> bool {
+    matches!(target, Target::Content | Target::Windows)
+}
+
+fn checks_macos(target: Target) -> bool {
+    matches!(target, Target::Content | Target::Mac)
+}
+
+/// Paths below `depot_root` that can not be installed on the platforms `target` ships to.
+pub fn check_depot_paths(depot_root: &Path, target: Target) -> anyhow::Result<Vec<String>> {
+    let mut problems = Vec::new();
+    for entry in WalkDir::new(depot_root).sort_by_file_name() {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(depot_root).unwrap();
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+        let Some(rel_str) = rel.to_str() else {
+            problems.push(format!("{}: not valid UTF-8", rel.display()));
+            continue;
+        };
+        let name = entry.file_name().to_string_lossy();
+
+        let mut reasons = Vec::new();
+        if name.len() > MAX_NAME_BYTES {
+            reasons.push(format!("name is longer than {MAX_NAME_BYTES} bytes"));
+        }
+        if name.chars().any(|c| c.is_control()) {
+            reasons.push("control character".to_string());
+        }
+        if checks_windows(target) {
+            reasons.extend(windows_name_problems(&name));
+            if first_too_long(rel_str, &name) {
+                reasons.push(format!(
+                    "path is longer than {WINDOWS_MAX_PATH} characters (Windows MAX_PATH)"
+                ));
+            }
+        }
+        if checks_macos(target) && name.contains(':') && !checks_windows(target) {
+            reasons.push("`:` is not allowed on macOS".to_string());
+        }
+
+        if !reasons.is_empty() {
+            problems.push(format!("{rel_str}: {}", reasons.join(", ")));
+        }
+    }
+    Ok(problems)
+}
+
+/// `rel_path` is longer than [`WINDOWS_MAX_PATH`] but its parent is not, so a long directory
+/// is reported once instead of for every file in it.
+fn first_too_long(rel_path: &str, name: &str) -> bool {
+    let len = rel_path.chars().count();
+    let parent_len = len - name.chars().count();
+    len > WINDOWS_MAX_PATH && parent_len <= WINDOWS_MAX_PATH
+}
+
+fn windows_name_problems(name: &str) -> Vec<String> {
+    let mut reasons = Vec::new();
+    let reserved: String = name
+        .chars()
+        .filter(|c| WINDOWS_RESERVED_CHARS.contains(c))
+        .collect();
+    if !reserved.is_empty() {
+        reasons.push(format!("`{reserved}` is not allowed on Windows"));
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        reasons.push("trailing dot or space is dropped on Windows".to_string());
+    }
+    // `CON`, `con.txt` and `Con.tar.gz` are all reserved
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    if WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        reasons.push(format!("`{stem}` is a reserved name on Windows"));
+    }
+    reasons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_plain_names() {
+        assert!(windows_name_problems("level_01.dat").is_empty());
+        assert!(windows_name_problems(".gitkeep").is_empty());
+        assert!(windows_name_problems("console.txt").is_empty());
+        assert!(windows_name_problems("COM10").is_empty());
+    }
+
+    #[test]
+    fn reports_reserved_chars_once() {
+        assert_eq!(
+            windows_name_problems("a<b>?.txt"),
+            ["`<>?` is not allowed on Windows"]
+        );
+    }
+
+    #[test]
+    fn reports_reserved_names_with_extensions() {
+        for name in ["CON", "con.txt", "Con.tar.gz", "lpt9.log", "NUL .txt"] {
+            let stem = name.split('.').next().unwrap().trim_end();
+            assert_eq!(
+                windows_name_problems(name),
+                [format!("`{stem}` is a reserved name on Windows")],
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn reports_trailing_dot_or_space() {
+        for name in ["readme.", "readme ", "aux."] {
+            assert!(
+                windows_name_problems(name)
+                    .iter()
+                    .any(|reason| reason.starts_with("trailing dot or space")),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn path_length_is_reported_for_the_first_long_component() {
+        let dir = "d".repeat(250);
+        assert!(!first_too_long(&format!("{dir}/file.bin"), "file.bin"));
+        let name = "n".repeat(20);
+        assert!(first_too_long(&format!("{dir}/{name}"), &name));
+        assert!(!first_too_long(
+            &format!("{dir}/{name}/file.bin"),
+            "file.bin"
+        ));
+    }
+
+    #[test]
+    fn path_length_counts_characters() {
+        // 260 characters, but more bytes
+        let name = "é".repeat(WINDOWS_MAX_PATH);
+        assert!(!first_too_long(&name, &name));
+        let name = format!("{name}x");
+        assert!(first_too_long(&name, &name));
+    }
+}
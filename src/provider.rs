@@ -0,0 +1,586 @@
+use crate::download::http_client;
+use crate::github::GitHubShortName;
+use crate::output::progress;
+use anyhow::{Context, anyhow};
+use reqwest::blocking::Response;
+use reqwest::header::{ACCEPT, AUTHORIZATION};
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Where the binaries releases are hosted, `provider` in the binaries block.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Provider {
+    #[default]
+    GitHub,
+    GitLab,
+    Gitea,
+}
+
+impl Provider {
+    pub fn name(self) -> &'static str {
+        match self {
+            Provider::GitHub => "github",
+            Provider::GitLab => "gitlab",
+            Provider::Gitea => "gitea",
+        }
+    }
+
+    /// `base_url` when the yini has none. Gitea is always self-hosted.
+    pub fn default_base_url(self) -> Option<&'static str> {
+        match self {
+            Provider::GitHub => Some("https://github.com"),
+            Provider::GitLab => Some("https://gitlab.com"),
+            Provider::Gitea => None,
+        }
+    }
+
+    /// Environment variable with the token for private releases.
+    pub fn token_env(self) -> &'static str {
+        match self {
+            Provider::GitHub => "GITHUB_TOKEN",
+            Provider::GitLab => "GITLAB_TOKEN",
+            Provider::Gitea => "GITEA_TOKEN",
+        }
+    }
+
+    /// User name that goes with the token for git over HTTPS.
+    pub fn git_username(self) -> &'static str {
+        match self {
+            Provider::GitHub => "x-access-token",
+            // any user name works with a token, this one is the convention
+            Provider::GitLab | Provider::Gitea => "oauth2",
+        }
+    }
+
+    fn api_url(self, base_url: &str) -> String {
+        let base_url = base_url.trim_end_matches('/');
+        match self {
+            Provider::GitHub if base_url == "https://github.com" => {
+                "https://api.github.com".to_string()
+            }
+            // GitHub Enterprise Server
+            Provider::GitHub => format!("{base_url}/api/v3"),
+            Provider::GitLab => format!("{base_url}/api/v4"),
+            Provider::Gitea => format!("{base_url}/api/v1"),
+        }
+    }
+
+    fn auth_headers(self, token: Option<&str>) -> Vec<(String, String)> {
+        let Some(token) = token else {
+            return Vec::new();
+        };
+        let header = match self {
+            Provider::GitHub => (AUTHORIZATION.to_string(), format!("Bearer {token}")),
+            // GitLab takes personal access tokens as bearer tokens too, and reqwest only drops
+            // `Authorization` when a redirect leaves the host
+            Provider::GitLab => (AUTHORIZATION.to_string(), format!("Bearer {token}")),
+            Provider::Gitea => (AUTHORIZATION.to_string(), format!("token {token}")),
+        };
+        vec![header]
+    }
+
+    /// Look up the release tagged `tag` and its assets.
+    pub fn resolve_release(
+        self,
+        base_url: &str,
+        repo: &GitHubShortName,
+        tag: &str,
+        token: Option<&str>,
+    ) -> anyhow::Result<Release> {
+        if self == Provider::GitHub && token.is_none() {
+            // public assets download straight from `releases/download`, without spending the
+            // 60 API requests an hour allowed without a token
+            return Ok(Release {
+                provider: self,
+                base_url: base_url.to_string(),
+                resolved_ref: tag.to_string(),
+                run: None,
+                assets: Vec::new(),
+                download_url: Some(format!(
+                    "{}/{}/{}/releases/download/{tag}",
+                    base_url.trim_end_matches('/'),
+                    repo.org,
+                    repo.name
+                )),
+            });
+        }
+        progress!(
+            "🏷️resolving release {tag} of {}/{} on {}",
+            repo.org,
+            repo.name,
+            self.name()
+        );
+        let project = format!("{}/{}", repo.org, repo.name);
+        let path: &[&str] = match self {
+            Provider::GitHub | Provider::Gitea => {
+                &["repos", &repo.org, &repo.name, "releases", "tags", tag]
+            }
+            // the project is `group/name` as one (encoded) segment
+            Provider::GitLab => &["projects", &project, "releases", tag],
+        };
+        let response = self.api_get(base_url, path, &[], token)?.ok_or_else(|| {
+            anyhow!(
+                "release {tag} not found in {project} on {} ({})",
+                self.name(),
+                self.private_repo_hint(token)
+            )
+        })?;
+
+        let assets = match self {
+            Provider::GitHub => {
+                let release: GitHubRelease = response.json().context("reading release")?;
+                release
+                    .assets
+                    .into_iter()
+                    .map(|asset| ReleaseAsset {
+                        name: asset.name,
+                        // the API URL also works for private repos, given the token
+                        url: if token.is_some() {
+                            asset.url
+                        } else {
+                            asset.browser_download_url
+                        },
+                    })
+                    .collect()
+            }
+            Provider::GitLab => {
+                let release: GitLabRelease = response.json().context("reading release")?;
+                release
+                    .assets
+                    .links
+                    .into_iter()
+                    .map(|link| ReleaseAsset {
+                        name: link.name,
+                        url: link.direct_asset_url.unwrap_or(link.url),
+                    })
+                    .collect()
+            }
+            Provider::Gitea => {
+                let release: GiteaRelease = response.json().context("reading release")?;
+                release
+                    .assets
+                    .into_iter()
+                    .map(|asset| ReleaseAsset {
+                        name: asset.name,
+                        url: asset.browser_download_url,
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(Release {
+            provider: self,
+            base_url: base_url.to_string(),
+            resolved_ref: tag.to_string(),
+            run: None,
+            assets,
+            download_url: None,
+        })
+    }
+
+    /// Look up the latest successful run of `workflow` (file name or id) on `branch` and its
+    /// artifacts. GitHub only.
+    pub fn resolve_workflow_run(
+        self,
+        base_url: &str,
+        repo: &GitHubShortName,
+        workflow: &str,
+        branch: &str,
+        token: Option<&str>,
+    ) -> anyhow::Result<Release> {
+        if self != Provider::GitHub {
+            return Err(anyhow!(
+                "`source actions` needs `provider github`, not {}",
+                self.name()
+            ));
+        }
+        // unlike the runs, the artifact zips are never public
+        let Some(token) = token else {
+            return Err(anyhow!(
+                "downloading workflow artifacts needs a token (--token-file, GITHUB_TOKEN or `gh auth login`)"
+            ));
+        };
+        progress!(
+            "🏷️resolving the latest successful {workflow} run on {branch} of {}/{}",
+            repo.org,
+            repo.name
+        );
+        let runs: GitHubRuns = self
+            .api_get(
+                base_url,
+                &[
+                    "repos",
+                    &repo.org,
+                    &repo.name,
+                    "actions",
+                    "workflows",
+                    workflow,
+                    "runs",
+                ],
+                &[("branch", branch), ("status", "success"), ("per_page", "1")],
+                Some(token),
+            )?
+            .ok_or_else(|| {
+                anyhow!(
+                    "workflow {workflow} not found in {}/{}",
+                    repo.org,
+                    repo.name
+                )
+            })?
+            .json()
+            .context("reading workflow runs")?;
+        let run = runs.workflow_runs.into_iter().next().ok_or_else(|| {
+            anyhow!(
+                "no successful run of {workflow} on {branch} in {}/{}",
+                repo.org,
+                repo.name
+            )
+        })?;
+
+        let run_id = run.id.to_string();
+        let artifacts: GitHubArtifacts = self
+            .api_get(
+                base_url,
+                &[
+                    "repos",
+                    &repo.org,
+                    &repo.name,
+                    "actions",
+                    "runs",
+                    &run_id,
+                    "artifacts",
+                ],
+                &[("per_page", "100")],
+                Some(token),
+            )?
+            .ok_or_else(|| anyhow!("workflow run {run_id} not found"))?
+            .json()
+            .context("reading workflow artifacts")?;
+        progress!("  run {run_id} of {}", run.head_sha);
+
+        Ok(Release {
+            provider: self,
+            base_url: base_url.to_string(),
+            resolved_ref: format!("refs/heads/{branch}"),
+            run: Some(WorkflowRun {
+                id: run.id,
+                head_sha: run.head_sha,
+            }),
+            assets: artifacts
+                .artifacts
+                .into_iter()
+                .filter(|artifact| !artifact.expired)
+                .map(|artifact| ReleaseAsset {
+                    name: artifact.name,
+                    url: artifact.archive_download_url,
+                })
+                .collect(),
+            download_url: None,
+        })
+    }
+
+    /// GET `path` below the API root, `None` for 404.
+    fn api_get(
+        self,
+        base_url: &str,
+        path: &[&str],
+        query: &[(&str, &str)],
+        token: Option<&str>,
+    ) -> anyhow::Result<Option<Response>> {
+        let mut url = Url::parse(&self.api_url(base_url))
+            .with_context(|| format!("binaries.base_url '{base_url}'"))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("binaries.base_url '{base_url}' is not a base URL"))?
+            .extend(path);
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+
+        let mut request = http_client()
+            .get(url.clone())
+            .header(ACCEPT, "application/json");
+        for (name, value) in self.auth_headers(token) {
+            request = request.header(name, value);
+        }
+        let response = request
+            .send()
+            .with_context(|| format!("requesting {url}"))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(anyhow!("{} {url}", response.status()));
+        }
+        Ok(Some(response))
+    }
+
+    fn private_repo_hint(self, token: Option<&str>) -> String {
+        match token {
+            Some(_) => format!("is {} allowed to read it?", self.token_env()),
+            None => format!("private repos need {}", self.token_env()),
+        }
+    }
+}
+
+impl FromStr for Provider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(Provider::GitHub),
+            "gitlab" => Ok(Provider::GitLab),
+            "gitea" => Ok(Provider::Gitea),
+            _ => Err(anyhow!("expected `github`, `gitlab` or `gitea`, got '{s}'")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkflowRun {
+    pub id: u64,
+    /// The commit the run built.
+    pub head_sha: String,
+}
+
+/// A release and its assets, or a workflow run and its artifacts.
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub provider: Provider,
+    pub base_url: String,
+    /// The tag (`v0.1.2`), or the branch of the workflow run (`refs/heads/main`).
+    pub resolved_ref: String,
+    pub run: Option<WorkflowRun>,
+    pub assets: Vec<ReleaseAsset>,
+    /// Set when the release was not looked up, every asset is downloaded from below it.
+    pub download_url: Option<String>,
+}
+
+impl Release {
+    pub fn asset(&self, name: &str) -> anyhow::Result<ReleaseAsset> {
+        if let Some(download_url) = &self.download_url {
+            return Ok(ReleaseAsset {
+                name: name.to_string(),
+                url: format!("{download_url}/{name}"),
+            });
+        }
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .cloned()
+            .ok_or_else(|| {
+                let names: Vec<&str> = self
+                    .assets
+                    .iter()
+                    .map(|asset| asset.name.as_str())
+                    .collect();
+                let what = match &self.run {
+                    Some(run) => format!("workflow run {} has no artifact", run.id),
+                    None => format!("release {} has no asset", self.resolved_ref),
+                };
+                anyhow!(
+                    "{what} {name} (it has: {})",
+                    if names.is_empty() {
+                        "none".to_string()
+                    } else {
+                        names.join(", ")
+                    }
+                )
+            })
+    }
+
+    /// Headers for downloading (or probing) the asset at `url`. Release links can point to any
+    /// host, so the token only goes to the provider itself.
+    pub fn download_headers(&self, url: &str, token: Option<&str>) -> Vec<(String, String)> {
+        if !self.is_provider_url(url) {
+            return Vec::new();
+        }
+        let mut headers = self.provider.auth_headers(token);
+        if self.provider == Provider::GitHub && self.run.is_none() && token.is_some() {
+            // the API asset URL returns JSON without it
+            headers.push((ACCEPT.to_string(), "application/octet-stream".to_string()));
+        }
+        headers
+    }
+
+    /// `url` is on the host of `base_url` or of its API (`api.github.com`).
+    fn is_provider_url(&self, url: &str) -> bool {
+        let host = |url: &str| {
+            Url::parse(url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+        };
+        let Some(url_host) = host(url) else {
+            return false;
+        };
+        [self.base_url.clone(), self.provider.api_url(&self.base_url)]
+            .iter()
+            .any(|provider_url| host(provider_url).as_deref() == Some(url_host.as_str()))
+    }
+}
+
+#[derive(Deserialize)]
+struct GitHubRelease {
+    assets: Vec<GitHubAsset>,
+}
+
+#[derive(Deserialize)]
+struct GitHubAsset {
+    name: String,
+    url: String,
+    browser_download_url: String,
+}
+
+#[derive(Deserialize)]
+struct GitHubRuns {
+    workflow_runs: Vec<GitHubRun>,
+}
+
+#[derive(Deserialize)]
+struct GitHubRun {
+    id: u64,
+    head_sha: String,
+}
+
+#[derive(Deserialize)]
+struct GitHubArtifacts {
+    artifacts: Vec<GitHubArtifact>,
+}
+
+#[derive(Deserialize)]
+struct GitHubArtifact {
+    name: String,
+    archive_download_url: String,
+    expired: bool,
+}
+
+#[derive(Deserialize)]
+struct GitLabRelease {
+    assets: GitLabAssets,
+}
+
+#[derive(Deserialize)]
+struct GitLabAssets {
+    #[serde(default)]
+    links: Vec<GitLabLink>,
+}
+
+#[derive(Deserialize)]
+struct GitLabLink {
+    name: String,
+    url: String,
+    direct_asset_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GiteaRelease {
+    assets: Vec<GiteaAsset>,
+}
+
+#[derive(Deserialize)]
+struct GiteaAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(provider: Provider, base_url: &str) -> Release {
+        Release {
+            provider,
+            base_url: base_url.to_string(),
+            resolved_ref: "v0.1.2".to_string(),
+            run: None,
+            assets: Vec::new(),
+            download_url: None,
+        }
+    }
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn github_token_goes_to_the_api_and_the_site() {
+        let release = release(Provider::GitHub, "https://github.com");
+        for url in [
+            "https://api.github.com/repos/org/eng/releases/assets/42",
+            "https://github.com/org/eng/releases/download/v0.1.2/eng-linux.zip",
+        ] {
+            let headers = release.download_headers(url, Some("ghtok"));
+            assert_eq!(
+                header(&headers, "authorization"),
+                Some("Bearer ghtok"),
+                "{url}"
+            );
+            assert_eq!(
+                header(&headers, "accept"),
+                Some("application/octet-stream"),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn github_token_never_goes_to_a_cdn_or_third_party() {
+        let release = release(Provider::GitHub, "https://github.com");
+        for url in [
+            "https://objects.githubusercontent.com/github-production-release-asset/42",
+            "https://release-assets.githubusercontent.com/42?sig=abc",
+            "https://cdn.example.com/eng-linux.zip",
+            "https://github.com.example.com/eng-linux.zip",
+            "not a url",
+        ] {
+            assert_eq!(release.download_headers(url, Some("ghtok")), [], "{url}");
+        }
+    }
+
+    #[test]
+    fn no_token_no_headers() {
+        let release = release(Provider::GitHub, "https://github.com");
+        let url = "https://api.github.com/repos/org/eng/releases/assets/42";
+        assert_eq!(release.download_headers(url, None), []);
+    }
+
+    #[test]
+    fn gitlab_token_stays_on_the_instance() {
+        let release = release(Provider::GitLab, "https://gitlab.example.com/");
+        let headers = release.download_headers(
+            "https://gitlab.example.com/api/v4/projects/org%2Feng/packages/generic/eng/0.1.2/eng-linux.zip",
+            Some("gltok"),
+        );
+        assert_eq!(
+            headers,
+            [("authorization".to_string(), "Bearer gltok".to_string())]
+        );
+
+        for url in [
+            "https://gitlab.com/org/eng/-/releases/v0.1.2/downloads/eng-linux.zip",
+            "https://storage.googleapis.com/gitlab-uploads/eng-linux.zip",
+        ] {
+            assert_eq!(release.download_headers(url, Some("gltok")), [], "{url}");
+        }
+    }
+
+    #[test]
+    fn gitea_uses_its_token_scheme() {
+        let release = release(Provider::Gitea, "https://gitea.example.com");
+        let url = "https://gitea.example.com/org/eng/releases/download/v0.1.2/eng-linux.zip";
+        let headers = release.download_headers(url, Some("gttok"));
+        assert_eq!(
+            headers,
+            [("authorization".to_string(), "token gttok".to_string())]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use anyhow::{Context, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Written into every directory boiler creates, so it only ever deletes its own directories.
+pub const BUILD_MARKER: &str = ".boiler-build";
+
+/// Decides whether a directory may be deleted.
+///
+/// Never deletes `/`, the home directory, the current directory, the config's directory,
+/// any parent of those, or a git repository root. Other directories need the
+/// [`BUILD_MARKER`] unless `force` is set.
+pub struct DeleteGuard {
+    protected: Vec<PathBuf>,
+    force: bool,
+}
+
+impl DeleteGuard {
+    pub fn new(config: Option<&Path>, force: bool) -> Self {
+        let mut protected = Vec::new();
+        if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+            protected.push(PathBuf::from(home));
+        }
+        if let Ok(cwd) = std::env::current_dir() {
+            protected.push(cwd);
+        }
+        if let Some(config_dir) = config.and_then(|config| config.canonicalize().ok()) {
+            protected.extend(config_dir.parent().map(Path::to_path_buf));
+        }
+
+        Self {
+            protected: protected
+                .into_iter()
+                .map(|p| p.canonicalize().unwrap_or(p))
+                .collect(),
+            force,
+        }
+    }
+
+    /// Ok if `dir` does not exist or may be deleted.
+    pub fn check(&self, dir: &Path) -> anyhow::Result<()> {
+        if !dir.exists() {
+            return Ok(());
+        }
+        let canonical = dir
+            .canonicalize()
+            .with_context(|| format!("resolving {}", dir.display()))?;
+
+        if canonical.parent().is_none() {
+            return Err(anyhow!(
+                "refusing to delete {dir:?}: it is a filesystem root"
+            ));
+        }
+        if let Some(protected) = self.protected.iter().find(|p| p.starts_with(&canonical)) {
+            return Err(anyhow!(
+                "refusing to delete {dir:?}: it is or contains {protected:?}"
+            ));
+        }
+        if canonical.join(".git").exists() {
+            return Err(anyhow!(
+                "refusing to delete {dir:?}: it is a git repository"
+            ));
+        }
+        if !self.force && !canonical.join(BUILD_MARKER).is_file() {
+            return Err(anyhow!(
+                "refusing to delete {dir:?}: it has no {BUILD_MARKER} marker, so boiler did not create it (pass --force if you are sure)"
+            ));
+        }
+        Ok(())
+    }
+
+    /// `fs::remove_dir_all`, after [`DeleteGuard::check`].
+    pub fn remove_dir_all(&self, dir: &Path) -> anyhow::Result<()> {
+        self.check(dir)?;
+        if dir.exists() {
+            fs::remove_dir_all(dir).with_context(|| format!("removing {}", dir.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Create `dir` and mark it as owned by boiler. A directory that already exists is left
+/// unmarked (unless it has the marker already): boiler did not create it, even if `--force`
+/// let it be used.
+pub fn create_marked_dir(dir: &Path) -> anyhow::Result<()> {
+    if dir.exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    let marker = dir.join(BUILD_MARKER);
+    fs::write(
+        &marker,
+        "created by boiler, which may delete this directory on the next run\n",
+    )
+    .with_context(|| format!("writing {}", marker.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_refused(guard: &DeleteGuard, dir: &Path, reason: &str) {
+        let err = guard.check(dir).unwrap_err().to_string();
+        assert!(err.contains(reason), "{err}");
+    }
+
+    #[test]
+    fn missing_dirs_may_be_deleted() {
+        let temp = tempfile::tempdir().unwrap();
+        let guard = DeleteGuard::new(None, false);
+        guard.check(&temp.path().join("missing")).unwrap();
+    }
+
+    #[test]
+    fn refuses_the_filesystem_root() {
+        let guard = DeleteGuard::new(None, true);
+        assert_refused(&guard, Path::new("/"), "it is a filesystem root");
+    }
+
+    #[test]
+    fn refuses_home_and_cwd_and_their_parents() {
+        let guard = DeleteGuard::new(None, true);
+        let cwd = std::env::current_dir().unwrap();
+        assert_refused(&guard, &cwd, "it is or contains");
+        assert_refused(&guard, cwd.parent().unwrap(), "it is or contains");
+        if let Some(home) = std::env::var_os("HOME") {
+            assert_refused(&guard, Path::new(&home), "it is or contains");
+        }
+    }
+
+    #[test]
+    fn refuses_the_config_dir() {
+        let temp = tempfile::tempdir().unwrap();
+        let config = temp.path().join("game/boiler.yini");
+        create_marked_dir(config.parent().unwrap()).unwrap();
+        fs::write(&config, "").unwrap();
+        let guard = DeleteGuard::new(Some(&config), true);
+        assert_refused(&guard, config.parent().unwrap(), "it is or contains");
+        assert_refused(&guard, temp.path(), "it is or contains");
+    }
+
+    #[test]
+    fn refuses_git_repositories() {
+        let temp = tempfile::tempdir().unwrap();
+        let repo = temp.path().join("repo");
+        create_marked_dir(&repo).unwrap();
+        fs::create_dir(repo.join(".git")).unwrap();
+        assert_refused(
+            &DeleteGuard::new(None, true),
+            &repo,
+            "it is a git repository",
+        );
+    }
+
+    #[test]
+    fn needs_the_marker_or_force() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("build");
+        fs::create_dir(&dir).unwrap();
+        assert_refused(
+            &DeleteGuard::new(None, false),
+            &dir,
+            "no .boiler-build marker",
+        );
+        DeleteGuard::new(None, true).check(&dir).unwrap();
+
+        let marked = temp.path().join("marked");
+        create_marked_dir(&marked).unwrap();
+        DeleteGuard::new(None, false).check(&marked).unwrap();
+    }
+
+    #[test]
+    fn existing_dirs_are_not_marked() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("mine");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("notes.txt"), "mine").unwrap();
+        create_marked_dir(&dir).unwrap();
+        assert!(!dir.join(BUILD_MARKER).exists());
+        assert_refused(
+            &DeleteGuard::new(None, false),
+            &dir,
+            "no .boiler-build marker",
+        );
+    }
+
+    #[test]
+    fn created_dirs_are_marked() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("build/nested");
+        create_marked_dir(&dir).unwrap();
+        assert!(dir.join(BUILD_MARKER).is_file());
+    }
+}
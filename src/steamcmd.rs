@@ -0,0 +1,237 @@
+use crate::output::{Event, emit, progress};
+use anyhow::{Context, anyhow};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+
+/// How many lines of steamcmd output to include in an error.
+const LOG_EXCERPT_LINES: usize = 30;
+
+#[derive(Debug)]
+pub struct UploadOutcome {
+    pub build_id: u64,
+    /// Every line steamcmd printed, stdout and stderr interleaved.
+    pub log: Vec<String>,
+}
+
+/// Run `steamcmd +login <login> +run_app_build <vdf> +quit`, echoing the output as it arrives.
+///
+/// steamcmd happily exits with status 0 after a failed build, so success is decided by
+/// finding the `Successfully finished AppID ... (BuildID ...)` line and no error lines.
+pub fn run_app_build(
+    steamcmd: &Path,
+    login: &str,
+    app_build_vdf: &Path,
+) -> anyhow::Result<UploadOutcome> {
+    progress!("🚀uploading {app_build_vdf:?} with {steamcmd:?}");
+
+    let mut child = Command::new(steamcmd)
+        .arg("+login")
+        .arg(login)
+        .arg("+run_app_build")
+        .arg(app_build_vdf)
+        .arg("+quit")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("running {}", steamcmd.display()))?;
+
+    let (sender, receiver) = mpsc::channel();
+    let stdout_reader = forward_lines(child.stdout.take().unwrap(), sender.clone());
+    let stderr_reader = forward_lines(child.stderr.take().unwrap(), sender);
+
+    let mut log = Vec::new();
+    let mut build_id = None;
+    let mut first_error_line = None;
+    for line in receiver {
+        progress!("  │ {line}");
+        emit(Event::SteamcmdOutput { line: line.clone() });
+        if build_id.is_none() {
+            build_id = parse_build_id(&line);
+        }
+        if first_error_line.is_none() && is_error_line(&line) {
+            first_error_line = Some(log.len());
+        }
+        log.push(line);
+    }
+
+    let _ = stdout_reader.join();
+    let _ = stderr_reader.join();
+    let status = child.wait().context("waiting for steamcmd")?;
+
+    let failure = if !status.success() {
+        Some(format!("steamcmd exited with {status}"))
+    } else if first_error_line.is_some() {
+        Some("steamcmd reported an error".to_string())
+    } else if build_id.is_none() {
+        Some("steamcmd finished without reporting a BuildID".to_string())
+    } else {
+        None
+    };
+
+    if let Some(reason) = failure {
+        return Err(anyhow!(
+            "{reason}:\n{}",
+            log_excerpt(&log, first_error_line)
+        ));
+    }
+
+    Ok(UploadOutcome {
+        build_id: build_id.unwrap(),
+        log,
+    })
+}
+
+fn forward_lines<R: Read + Send + 'static>(
+    reader: R,
+    sender: mpsc::Sender<String>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&buf);
+                    let line = line.trim_end_matches(['\r', '\n']);
+                    if sender.send(line.to_string()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// `Successfully finished AppID 1234560 build (BuildID 9876543).`
+fn parse_build_id(line: &str) -> Option<u64> {
+    if !line.contains("Successfully finished") {
+        return None;
+    }
+    let after = &line[line.find("BuildID")? + "BuildID".len()..];
+    let digits: String = after
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+fn is_error_line(line: &str) -> bool {
+    line.contains("ERROR!") || line.contains("FAILED") || line.contains("Login Failure")
+}
+
+/// The lines around the first error, or the tail of the log if no error line was seen.
+fn log_excerpt(log: &[String], first_error_line: Option<usize>) -> String {
+    let start = match first_error_line {
+        Some(index) => index.saturating_sub(LOG_EXCERPT_LINES / 2),
+        None => log.len().saturating_sub(LOG_EXCERPT_LINES),
+    };
+    let end = (start + LOG_EXCERPT_LINES).min(log.len());
+    log[start..end]
+        .iter()
+        .map(|line| format!("  │ {line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUCCESSFUL_BUILD: &str = "\
+Redirecting stderr to '/home/builder/Steam/logs/stderr.txt'
+[  0%] Checking for available updates...
+[----] Verifying installation...
+Steam Console Client (c) Valve Corporation - version 1716584667
+-- type 'quit' to exit --
+Loading Steam API...OK
+Logging in user 'builder' to Steam Public...OK
+Waiting for client config...OK
+Waiting for user info...OK
+[2025-03-01 10:21:03]: Starting AppID 1234560 build (flags 0x0).
+[2025-03-01 10:21:03]: Building depot 1234561...
+[2025-03-01 10:21:05]: DepotBuild 1234561 - manifest 8134906237492012384
+[2025-03-01 10:21:06]: Committing build...
+[2025-03-01 10:21:07]: Successfully finished AppID 1234560 build (BuildID 9876543).
+Unloading Steam API...OK";
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn build_id_from_the_finished_line() {
+        let build_ids: Vec<u64> = SUCCESSFUL_BUILD
+            .lines()
+            .filter_map(parse_build_id)
+            .collect();
+        assert_eq!(build_ids, [9876543]);
+        assert!(!SUCCESSFUL_BUILD.lines().any(is_error_line));
+    }
+
+    #[test]
+    fn build_id_needs_a_finished_build() {
+        assert_eq!(
+            parse_build_id(
+                "[2025-03-01 10:21:03]: Starting AppID 1234560 build (BuildID 9876543)."
+            ),
+            None
+        );
+        assert_eq!(
+            parse_build_id("Successfully finished AppID 1234560 build."),
+            None
+        );
+        assert_eq!(
+            parse_build_id("Successfully finished AppID 1234560 build (BuildID )."),
+            None
+        );
+    }
+
+    #[test]
+    fn error_lines() {
+        for line in [
+            "ERROR! Failed to commit build for AppID 1234560 : Failure",
+            "[2025-03-01 10:21:05]: ERROR! Content root folder 'content' does not exist",
+            "Logging in user 'builder' to Steam Public...FAILED (Invalid Password)",
+            "Login Failure: Invalid Password",
+        ] {
+            assert!(is_error_line(line), "{line}");
+        }
+        for line in [
+            "Logging in user 'builder' to Steam Public...OK",
+            "[2025-03-01 10:21:03]: Building depot 1234561...",
+            "Successfully finished AppID 1234560 build (BuildID 9876543).",
+        ] {
+            assert!(!is_error_line(line), "{line}");
+        }
+    }
+
+    #[test]
+    fn excerpt_is_the_tail_without_an_error() {
+        let log: Vec<String> = (0..50).map(|n| format!("line {n}")).collect();
+        let excerpt = log_excerpt(&log, None);
+        let excerpt: Vec<&str> = excerpt.lines().collect();
+        assert_eq!(excerpt.len(), LOG_EXCERPT_LINES);
+        assert_eq!(excerpt[0], "  │ line 20");
+        assert_eq!(excerpt[LOG_EXCERPT_LINES - 1], "  │ line 49");
+    }
+
+    #[test]
+    fn excerpt_surrounds_the_first_error() {
+        let log: Vec<String> = (0..100).map(|n| format!("line {n}")).collect();
+        let excerpt = log_excerpt(&log, Some(40));
+        let excerpt: Vec<&str> = excerpt.lines().collect();
+        assert_eq!(excerpt.len(), LOG_EXCERPT_LINES);
+        assert_eq!(excerpt[0], "  │ line 25");
+        assert!(excerpt.contains(&"  │ line 40"));
+
+        let log = lines(SUCCESSFUL_BUILD);
+        assert_eq!(log_excerpt(&log, Some(1)).lines().count(), log.len());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::output::{progress, register_secret};
+use crate::provider::Provider;
+use anyhow::{Context, anyhow};
+use reqwest::Url;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::{env, fs};
+
+/// Token for `provider` at `base_url`, from the first of: `token_file`, `github_token`
+/// (`--github-token`, GitHub only), the provider's variable (`GITHUB_TOKEN`, ...),
+/// `gh auth token` (GitHub only) and, with `from_git_credential`, the git credential helper. The
+/// token is registered as a secret, so it never shows up in the output.
+pub fn resolve_token(
+    provider: Provider,
+    base_url: &str,
+    token_file: Option<&Path>,
+    github_token: Option<&str>,
+    from_git_credential: bool,
+) -> anyhow::Result<Option<String>> {
+    let url = Url::parse(base_url).with_context(|| format!("binaries.base_url '{base_url}'"))?;
+    let host = url.host_str().unwrap_or_default();
+    let found = match token_file {
+        Some(path) => Some((read_token_file(path)?, format!("{path:?}"))),
+        None => github_token
+            .filter(|_| provider == Provider::GitHub)
+            .map(|token| (token.to_string(), "--github-token".to_string()))
+            .or_else(|| {
+                env::var(provider.token_env())
+                    .ok()
+                    .map(|token| (token, provider.token_env().to_string()))
+            })
+            .filter(|(token, _)| !token.trim().is_empty())
+            .or_else(|| {
+                (provider == Provider::GitHub)
+                    .then(|| gh_auth_token(host))
+                    .flatten()
+                    .map(|token| (token, "gh auth token".to_string()))
+            })
+            .or_else(|| {
+                // whatever is stored may be stale, or a password, and would break public releases
+                from_git_credential
+                    .then(|| credential_helper_token(url.scheme(), &host_and_port(&url)))
+                    .flatten()
+                    .map(|token| (token, "the git credential helper".to_string()))
+            }),
+    };
+
+    let Some((token, source)) = found else {
+        return Ok(None);
+    };
+    let token = token.trim().to_string();
+    register_secret(&token);
+    progress!(
+        "🔑using the {} token for {host} from {source}",
+        provider.name()
+    );
+    Ok(Some(token))
+}
+
+/// Host (and port) of an `https://` URL, git only gets a token for those.
+pub fn https_host(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    (url.scheme() == "https").then(|| host_and_port(&url))
+}
+
+/// `host` or `host:port`, how git's credential helpers tell hosts apart.
+fn host_and_port(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    }
+}
+
+fn read_token_file(path: &Path) -> anyhow::Result<String> {
+    let token = fs::read_to_string(path)
+        .with_context(|| format!("reading the token file {}", path.display()))?;
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(anyhow!("the token file {} is empty", path.display()));
+    }
+    Ok(token.to_string())
+}
+
+/// The token the GitHub CLI is logged in with, `None` when it is not installed or logged in.
+fn gh_auth_token(host: &str) -> Option<String> {
+    let output = Command::new("gh")
+        .args(["auth", "token", "--hostname", host])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !token.is_empty()).then_some(token)
+}
+
+/// The password git's credential helper has stored for `<protocol>://<host>`, without ever
+/// prompting for one.
+fn credential_helper_token(protocol: &str, host: &str) -> Option<String> {
+    if host.is_empty() {
+        return None;
+    }
+    let mut child = Command::new("git")
+        .args(["credential", "fill"])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        // an empty GIT_ASKPASS also skips core.askPass and SSH_ASKPASS
+        .env("GIT_ASKPASS", "")
+        // Git Credential Manager would open a browser to log in
+        .env("GCM_INTERACTIVE", "never")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    child
+        .stdin
+        .take()?
+        .write_all(format!("protocol={protocol}\nhost={host}\n\n").as_bytes())
+        .ok()?;
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("password="))
+        .filter(|password| !password.is_empty())
+        .map(str::to_string)
+}
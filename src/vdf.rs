@@ -1,10 +1,13 @@
 use crate::{Depot, DepotId, SteamAppId};
 use std::path::Path;
 
+/// `build_output` is where steamcmd writes its build logs and depot manifests,
+/// must be an absolute path.
 pub fn app_build(
     app_id: SteamAppId,
     description: &str,
     live_branch: Option<&str>,
+    build_output: &Path,
     depots: &[Depot],
 ) -> String {
     let mut depots_string = String::new();
@@ -21,11 +24,14 @@ pub fn app_build(
         None => String::new(),
     };
 
+    let build_output = build_output.to_str().unwrap();
+
     let vdf = format!(
         r#""AppBuild"
 {{
     "appid"    "{app_id}"
     "desc"     "{description}"
+    "buildoutput" "{build_output}"
 {setlive_section}    "depots"
     {{
 {depots_string}    }}
@@ -35,6 +41,15 @@ pub fn app_build(
     vdf
 }
 
+/// Reads back the `"setlive"` branch from a generated app_build VDF.
+pub fn setlive_branch(app_build_vdf: &str) -> Option<String> {
+    app_build_vdf.lines().find_map(|line| {
+        let rest = line.trim().strip_prefix("\"setlive\"")?;
+        let branch = rest.trim().trim_matches('"');
+        (!branch.is_empty()).then(|| branch.to_string())
+    })
+}
+
 pub fn depot(depot_id: DepotId, content_root: &Path) -> String {
     let absolute_path = content_root
         .canonicalize()
@@ -1,35 +1,238 @@
-use crate::github::GitHubShortName;
+use crate::buildinfo::BuildInfoFormat;
+use crate::download::EnvHeader;
+use crate::fsutil::{CollisionPolicy, CopyMapping, CopyMode, DEFAULT_EXCLUDES, has_glob_meta};
+use crate::git::{CloneOptions, LfsFetch, Submodules};
+use crate::github::{GitHubShortName, github_repo_url};
+use crate::provider::Provider;
 use crate::{DepotId, SteamAppId};
+use anyhow::{Context, anyhow};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use tracing::info;
 
+/// Optional per-depot `max_size` and `max_file_size`, in bytes.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SizeBudget {
+    pub max_size: Option<u64>,
+    pub max_file_size: Option<u64>,
+}
+
+impl SizeBudget {
+    pub fn is_set(&self) -> bool {
+        self.max_size.is_some() || self.max_file_size.is_some()
+    }
+}
+
+/// Where the binaries of one platform come from, `url`, `path` or `artifact` in the platform
+/// block.
+#[derive(Debug, Clone, Default)]
+pub enum BinariesSource {
+    /// The asset of the GitHub release (`repo`, `name` and `version`).
+    #[default]
+    Release,
+    /// The named artifact of the latest successful workflow run (`source actions`).
+    Artifact(String),
+    /// An archive at any HTTP(S) URL.
+    Url {
+        url: String,
+        headers: Vec<EnvHeader>,
+    },
+    /// A local archive, extracted, or directory, copied.
+    Path(PathBuf),
+}
+
 #[derive(Debug)]
 pub struct BinariesPlatform {
     pub depot: DepotId,
+    pub budget: SizeBudget,
+    pub source: BinariesSource,
+}
+
+/// `workflow` and `branch` of `source actions` in the binaries block.
+#[derive(Debug, Clone)]
+pub struct ActionsYini {
+    /// File name (`nightly.yml`) or id of the workflow.
+    pub workflow: String,
+    pub branch: String,
 }
 
 #[derive(Debug)]
 pub struct BinariesYini {
+    pub provider: Provider,
+    /// `base_url`, or the default of the provider.
+    pub base_url: String,
     pub repo: GitHubShortName,
     pub name: String, // release name
     pub version: String,
+    /// Set for `source actions`, the platforms then download workflow artifacts instead of
+    /// release assets.
+    pub actions: Option<ActionsYini>,
     pub macos: BinariesPlatform,
     pub windows: BinariesPlatform,
     pub linux: BinariesPlatform,
 }
 
+impl BinariesYini {
+    /// Some platform is downloaded from the GitHub release.
+    pub fn uses_release(&self) -> bool {
+        [&self.macos, &self.windows, &self.linux]
+            .iter()
+            .any(|platform| matches!(platform.source, BinariesSource::Release))
+    }
+
+    /// Some platform is downloaded from a workflow run.
+    pub fn uses_actions(&self) -> bool {
+        [&self.macos, &self.windows, &self.linux]
+            .iter()
+            .any(|platform| matches!(platform.source, BinariesSource::Artifact(_)))
+    }
+}
+
+/// Where the content comes from, `repo` or `path` in the content block.
+#[derive(Debug, Clone)]
+pub enum ContentSource {
+    /// Cloned. `name` is `org/name` for GitHub repos, else the URL.
+    Git { url: String, name: String },
+    /// A local directory, copied from as it is.
+    Path(PathBuf),
+}
+
+impl ContentSource {
+    /// `org/name`, URL or path, for the build info.
+    pub fn name(&self) -> String {
+        match self {
+            ContentSource::Git { name, .. } => name.clone(),
+            ContentSource::Path(path) => path.display().to_string(),
+        }
+    }
+
+    /// URL or path, for progress and reports.
+    pub fn location(&self) -> String {
+        match self {
+            ContentSource::Git { url, .. } => url.clone(),
+            ContentSource::Path(path) => path.display().to_string(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ContentYini {
     pub depot: DepotId,
-    pub repo: GitHubShortName,
-    pub copy: Vec<(String, PathBuf)>,
+    pub budget: SizeBudget,
+    pub source: ContentSource,
+    pub copy: Vec<CopyMapping>,
+    /// gitignore-style patterns left out of every copy mapping.
+    pub exclude: Vec<String>,
+    /// Also exclude [`DEFAULT_EXCLUDES`] (OS junk files), `default_excludes false` turns it off.
+    pub default_excludes: bool,
+    /// Only download and check out what `copy` needs, `sparse false` clones everything.
+    pub sparse: bool,
+    pub submodules: Submodules,
+    pub lfs: LfsMode,
+}
+
+/// Which Git LFS objects to pull, `lfs` in the content block. Only used if the repo has LFS
+/// files.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum LfsMode {
+    #[default]
+    All,
+    /// Only the objects below the `copy` sources.
+    Copied,
+    Off,
+}
+
+impl LfsMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            LfsMode::All => "all",
+            LfsMode::Copied => "copied",
+            LfsMode::Off => "off",
+        }
+    }
+}
+
+impl FromStr for LfsMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(LfsMode::All),
+            "copied" => Ok(LfsMode::Copied),
+            "off" => Ok(LfsMode::Off),
+            _ => Err(anyhow!("expected `all`, `copied` or `off`, got '{s}'")),
+        }
+    }
+}
+
+impl ContentYini {
+    /// `exclude`, after the default excludes if they are enabled.
+    pub fn all_excludes(&self) -> Vec<String> {
+        let defaults = if self.default_excludes {
+            DEFAULT_EXCLUDES
+        } else {
+            &[]
+        };
+        // a local working copy still has its `.git`, clones do not
+        let git = matches!(self.source, ContentSource::Path(_)).then_some(".git");
+        git.iter()
+            .chain(defaults)
+            .map(|pattern| pattern.to_string())
+            .chain(self.exclude.iter().cloned())
+            .collect()
+    }
+
+    pub fn clone_options(&self) -> CloneOptions {
+        CloneOptions {
+            // the copy sources, or everything
+            sparse_paths: self.sparse.then(|| {
+                self.copy
+                    .iter()
+                    .map(|mapping| mapping.from.to_string_lossy().to_string())
+                    .collect()
+            }),
+            submodules: self.submodules,
+            lfs: self.lfs_fetch(),
+            // from `--cache-dir`
+            mirror: None,
+        }
+    }
+
+    fn lfs_fetch(&self) -> LfsFetch {
+        match self.lfs {
+            LfsMode::All => LfsFetch::All,
+            LfsMode::Off => LfsFetch::Off,
+            LfsMode::Copied => LfsFetch::Paths(
+                self.copy
+                    .iter()
+                    .flat_map(|mapping| {
+                        let source = mapping.from.to_string_lossy();
+                        let source = source.trim_matches('/');
+                        if has_glob_meta(source) {
+                            vec![source.to_string()]
+                        } else {
+                            // the file itself, or everything below the directory
+                            vec![source.to_string(), format!("{source}/**")]
+                        }
+                    })
+                    .collect(),
+            ),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct BoilerYini {
     pub app_id: SteamAppId,
+    /// Steam branches that may be set live. Empty means any (except `default`/`public`).
+    pub allowed_branches: Vec<String>,
+    /// Steam branches that need `--yes` or an interactive confirmation to set live.
+    pub protected_branches: Vec<String>,
+    /// Which build info files are written into each depot.
+    pub buildinfo_format: BuildInfoFormat,
+    /// Overwritten files and case-only duplicates in a depot fail the build or are warnings.
+    pub on_collision: CollisionPolicy,
     pub binaries: BinariesYini,
     pub content: ContentYini,
 }
@@ -38,104 +241,893 @@ impl Default for BoilerYini {
     fn default() -> Self {
         Self {
             app_id: 0,
+            allowed_branches: Vec::new(),
+            protected_branches: Vec::new(),
+            buildinfo_format: BuildInfoFormat::default(),
+            on_collision: CollisionPolicy::default(),
             binaries: BinariesYini {
+                provider: Provider::default(),
+                base_url: String::new(),
                 repo: GitHubShortName {
                     org: "".to_string(),
                     name: "".to_string(),
                 },
                 name: "".to_string(),
                 version: "".to_string(),
-                macos: BinariesPlatform { depot: 0 },
-                windows: BinariesPlatform { depot: 0 },
-                linux: BinariesPlatform { depot: 0 },
+                actions: None,
+                macos: BinariesPlatform {
+                    depot: 0,
+                    budget: SizeBudget::default(),
+                    source: BinariesSource::default(),
+                },
+                windows: BinariesPlatform {
+                    depot: 0,
+                    budget: SizeBudget::default(),
+                    source: BinariesSource::default(),
+                },
+                linux: BinariesPlatform {
+                    depot: 0,
+                    budget: SizeBudget::default(),
+                    source: BinariesSource::default(),
+                },
             },
             content: ContentYini {
                 depot: 0,
-                repo: GitHubShortName {
-                    org: "".to_string(),
-                    name: "".to_string(),
-                },
+                budget: SizeBudget::default(),
+                source: ContentSource::Path(PathBuf::new()),
                 copy: Vec::new(),
+                exclude: Vec::new(),
+                default_excludes: true,
+                sparse: true,
+                submodules: Submodules::default(),
+                lfs: LfsMode::default(),
             },
         }
     }
 }
 
-pub fn parse_yini(yini_path: &Path) -> Option<BoilerYini> {
+pub fn parse_yini(yini_path: &Path) -> anyhow::Result<BoilerYini> {
     info!(?yini_path, "⚙️reading the lovely yini file");
 
-    let mut ini = BoilerYini::default();
+    let str = fs::read_to_string(yini_path)
+        .with_context(|| format!("reading {}", yini_path.display()))?;
 
-    let Ok(str) = fs::read_to_string(yini_path) else {
-        return None;
-    };
+    parse_yini_str(&str, yini_path)
+}
 
-    let mut parser = yini::Parser::new(&str);
+/// [`parse_yini`] for the text of `yini_path`.
+fn parse_yini_str(str: &str, yini_path: &Path) -> anyhow::Result<BoilerYini> {
+    let mut ini = BoilerYini::default();
+
+    let mut parser = yini::Parser::new(str);
 
     let root = parser.parse();
 
-    ini.app_id = root.get("steam_app_id").unwrap().as_int().unwrap() as SteamAppId;
+    if let Some(error) = parser.errors().first() {
+        let hint = match error.kind {
+            // yini reads barewords like `txt` or `filtered` as a `true` or `false` gone wrong
+            yini::ErrorKind::InvalidBooleanLiteral => {
+                " (words starting with t or f must be quoted, e.g. \"txt\")"
+            }
+            _ => "",
+        };
+        return Err(anyhow!(
+            "{}:{}:{}: {:?}{hint}",
+            yini_path.display(),
+            error.line,
+            error.column,
+            error.kind
+        ));
+    }
 
-    let binaries_root = root.get("binaries").unwrap().as_object().unwrap();
+    ini.app_id = int(&root, "steam_app_id")? as SteamAppId;
 
-    {
-        let repo = binaries_root
-            .get("repo")
-            .unwrap()
-            .as_str()
-            .unwrap()
-            .to_string();
+    ini.allowed_branches = string_array(&root, "allowed_branches");
+    ini.protected_branches = string_array(&root, "protected_branches");
 
-        ini.binaries.repo = GitHubShortName::from_str(&repo).unwrap();
-        ini.binaries.name = binaries_root
-            .get("name")
-            .unwrap()
+    if let Some(format) = root.get("buildinfo_format") {
+        let format = format
             .as_str()
-            .unwrap()
-            .to_string();
+            .ok_or_else(|| anyhow!("buildinfo_format: expected a string"))?;
+        ini.buildinfo_format = format.parse().context("buildinfo_format")?;
+    }
 
-        ini.binaries.version = binaries_root
-            .get("version")
-            .unwrap()
+    if let Some(policy) = root.get("on_collision") {
+        let policy = policy
             .as_str()
-            .unwrap()
-            .to_string();
+            .ok_or_else(|| anyhow!("on_collision: expected a string"))?;
+        ini.on_collision = policy.parse().context("on_collision")?;
+    }
+
+    let binaries_root = object(&root, "binaries")?;
+    {
+        ini.binaries.actions = actions_yini(binaries_root)?;
+        let actions = ini.binaries.actions.is_some();
 
-        let windows_root = binaries_root.get("windows").unwrap().as_object().unwrap();
+        let windows_root = object(binaries_root, "windows").context("binaries")?;
         ini.binaries.windows.depot =
-            windows_root.get("depot").unwrap().as_int().unwrap() as DepotId;
+            int(windows_root, "depot").context("binaries.windows")? as DepotId;
+        ini.binaries.windows.budget = size_budget(windows_root).context("binaries.windows")?;
+        ini.binaries.windows.source =
+            binaries_source(windows_root, yini_path, actions).context("binaries.windows")?;
 
-        let linux_root = binaries_root.get("linux").unwrap().as_object().unwrap();
-        ini.binaries.linux.depot = linux_root.get("depot").unwrap().as_int().unwrap() as DepotId;
+        let linux_root = object(binaries_root, "linux").context("binaries")?;
+        ini.binaries.linux.depot = int(linux_root, "depot").context("binaries.linux")? as DepotId;
+        ini.binaries.linux.budget = size_budget(linux_root).context("binaries.linux")?;
+        ini.binaries.linux.source =
+            binaries_source(linux_root, yini_path, actions).context("binaries.linux")?;
 
-        let macos_root = binaries_root.get("macos").unwrap().as_object().unwrap();
-        ini.binaries.macos.depot = macos_root.get("depot").unwrap().as_int().unwrap() as DepotId;
+        let macos_root = object(binaries_root, "macos").context("binaries")?;
+        ini.binaries.macos.depot = int(macos_root, "depot").context("binaries.macos")? as DepotId;
+        ini.binaries.macos.budget = size_budget(macos_root).context("binaries.macos")?;
+        ini.binaries.macos.source =
+            binaries_source(macos_root, yini_path, actions).context("binaries.macos")?;
+
+        // only needed for the platforms without their own `url` or `path`
+        let uses_release = ini.binaries.uses_release();
+        let uses_provider = uses_release || ini.binaries.uses_actions();
+        if let Some(provider) = binaries_root.get("provider") {
+            let provider = provider
+                .as_str()
+                .ok_or_else(|| anyhow!("binaries.provider: expected a string"))?;
+            ini.binaries.provider = provider.parse().context("binaries.provider")?;
+        }
+        if actions && ini.binaries.provider != Provider::GitHub {
+            return Err(anyhow!(
+                "binaries: `source actions` needs `provider github`, not {}",
+                ini.binaries.provider.name()
+            ));
+        }
+        ini.binaries.base_url = match binaries_root.get("base_url") {
+            Some(_) => string(binaries_root, "base_url")
+                .context("binaries")?
+                .trim_end_matches('/')
+                .to_string(),
+            None => ini
+                .binaries
+                .provider
+                .default_base_url()
+                .unwrap_or_default()
+                .to_string(),
+        };
+        if uses_provider && ini.binaries.base_url.is_empty() {
+            return Err(anyhow!(
+                "binaries: `provider {}` needs a `base_url`",
+                ini.binaries.provider.name()
+            ));
+        }
+        if uses_provider || binaries_root.get("repo").is_some() {
+            ini.binaries.repo = if ini.binaries.provider == Provider::GitLab {
+                gitlab_project(binaries_root)?
+            } else {
+                github_short_name(binaries_root, "binaries.repo")?
+            };
+        }
+        if uses_release || binaries_root.get("name").is_some() {
+            ini.binaries.name = string(binaries_root, "name")
+                .context("binaries")?
+                .to_string();
+        }
+        if uses_release || binaries_root.get("version").is_some() {
+            ini.binaries.version = string(binaries_root, "version")
+                .context("binaries")?
+                .to_string();
+        }
     }
 
-    let content_root = root.get("content").unwrap().as_object().unwrap();
+    let content_root = object(&root, "content")?;
     {
-        ini.content.depot = content_root.get("depot").unwrap().as_int().unwrap() as DepotId;
-
-        let repo = content_root
-            .get("repo")
-            .unwrap()
-            .as_str()
-            .unwrap()
-            .to_string();
-        ini.content.repo = GitHubShortName::from_str(&repo).unwrap();
+        ini.content.depot = int(content_root, "depot").context("content")? as DepotId;
+        ini.content.budget = size_budget(content_root).context("content")?;
+        ini.content.source = content_source(content_root, yini_path)?;
 
         let mut converted = Vec::new();
 
-        for v in content_root.get("copy").unwrap().as_array().unwrap() {
-            let (key, value) = v.as_tuple().unwrap();
-            converted.push((
-                key.as_str().unwrap().to_string(),
-                Path::new(&value.as_str().unwrap()).to_path_buf(),
-            ));
+        let copy = content_root
+            .get("copy")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| anyhow!("content: missing `copy` list"))?;
+        for v in copy {
+            converted.push(copy_mapping(v).context("content.copy")?);
         }
 
         ini.content.copy = converted;
+        ini.content.exclude = string_array(content_root, "exclude");
+        if let Some(default_excludes) = content_root.get("default_excludes") {
+            ini.content.default_excludes = default_excludes
+                .as_bool()
+                .ok_or_else(|| anyhow!("content.default_excludes: expected true or false"))?;
+        }
+        if let Some(sparse) = content_root.get("sparse") {
+            ini.content.sparse = sparse
+                .as_bool()
+                .ok_or_else(|| anyhow!("content.sparse: expected true or false"))?;
+        }
+        if let Some(submodules) = content_root.get("submodules") {
+            let submodules = submodules
+                .as_str()
+                .ok_or_else(|| anyhow!("content.submodules: expected a string"))?;
+            ini.content.submodules = submodules.parse().context("content.submodules")?;
+        }
+        if let Some(lfs) = content_root.get("lfs") {
+            let lfs = lfs
+                .as_str()
+                .ok_or_else(|| anyhow!("content.lfs: expected a string"))?;
+            ini.content.lfs = lfs.parse().context("content.lfs")?;
+        }
+    }
+
+    Ok(ini)
+}
+
+/// `"source" "target"` or `"source" { to "target" mode rename exclude [ ... ] }` (one key per
+/// line).
+fn copy_mapping(value: &yini::Value) -> anyhow::Result<CopyMapping> {
+    let (source, target) = value
+        .as_tuple()
+        .ok_or_else(|| anyhow!("expected \"source\" \"target\" pairs"))?;
+    let source = source
+        .as_str()
+        .ok_or_else(|| anyhow!("source must be a string"))?;
+
+    if let Some(target) = target.as_str() {
+        return Ok(CopyMapping {
+            from: PathBuf::from(source),
+            to: PathBuf::from(target),
+            mode: CopyMode::default(),
+            exclude: Vec::new(),
+        });
+    }
+
+    let options = target
+        .as_object()
+        .ok_or_else(|| anyhow!("{source}: target must be a string or a `{{ to ... }}` block"))?;
+    let mode = match options.get("mode") {
+        Some(mode) => mode
+            .as_str()
+            .ok_or_else(|| anyhow!("{source}: `mode` must be a string"))?
+            .parse()
+            .with_context(|| source.to_string())?,
+        None => CopyMode::default(),
+    };
+    if mode == CopyMode::Rename && has_glob_meta(source) {
+        return Err(anyhow!(
+            "{source}: rename needs a single source, not a glob (use into-dir or keep-dir-name)"
+        ));
+    }
+    Ok(CopyMapping {
+        from: PathBuf::from(source),
+        to: PathBuf::from(string(options, "to").with_context(|| source.to_string())?),
+        mode,
+        exclude: string_array(options, "exclude"),
+    })
+}
+
+fn object<'a>(parent: &'a yini::Object, key: &str) -> anyhow::Result<&'a yini::Object> {
+    parent
+        .get(key)
+        .and_then(|v| v.as_object())
+        .ok_or_else(|| anyhow!("missing `{key} {{ ... }}` block"))
+}
+
+fn string<'a>(parent: &'a yini::Object, key: &str) -> anyhow::Result<&'a str> {
+    parent
+        .get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow!("missing string `{key}`"))
+}
+
+fn int(parent: &yini::Object, key: &str) -> anyhow::Result<i64> {
+    parent
+        .get(key)
+        .and_then(|v| v.as_int())
+        .ok_or_else(|| anyhow!("missing integer `{key}`"))
+}
+
+fn github_short_name(parent: &yini::Object, name: &str) -> anyhow::Result<GitHubShortName> {
+    let key = name.rsplit('.').next().unwrap();
+    let repo = string(parent, key).with_context(|| name.to_string())?;
+    GitHubShortName::from_str(repo)
+        .map_err(|_| anyhow!("{name}: expected `org/name`, got '{repo}'"))
+}
+
+/// `repo` (GitHub `org/name` or a git URL) or `path` (relative to the yini file).
+fn content_source(content_root: &yini::Object, yini_path: &Path) -> anyhow::Result<ContentSource> {
+    match (content_root.get("repo"), content_root.get("path")) {
+        (Some(_), Some(_)) => Err(anyhow!("content: set either `repo` or `path`, not both")),
+        (None, None) => Err(anyhow!(
+            "content: missing `repo` (`org/name` or a git URL) or `path` (a local directory)"
+        )),
+        (Some(_), None) => {
+            let repo = string(content_root, "repo").context("content")?;
+            if repo.contains("://") || is_scp_like_url(repo) {
+                return Ok(ContentSource::Git {
+                    url: repo.to_string(),
+                    name: repo.to_string(),
+                });
+            }
+            let short_name = GitHubShortName::from_str(repo).map_err(|_| {
+                anyhow!("content.repo: expected `org/name` or a git URL, got '{repo}'")
+            })?;
+            Ok(ContentSource::Git {
+                url: github_repo_url(&short_name),
+                name: format!("{}/{}", short_name.org, short_name.name),
+            })
+        }
+        (None, Some(_)) => {
+            let path = string(content_root, "path").context("content")?;
+            let yini_dir = yini_path.parent().unwrap_or(Path::new(""));
+            Ok(ContentSource::Path(yini_dir.join(path)))
+        }
+    }
+}
+
+/// `source actions` with `workflow` and `branch` (default `main`), `None` for `source release`
+/// (default).
+fn actions_yini(binaries_root: &yini::Object) -> anyhow::Result<Option<ActionsYini>> {
+    let source = match binaries_root.get("source") {
+        Some(_) => string(binaries_root, "source").context("binaries")?,
+        None => "release",
+    };
+    match source {
+        "release" => {
+            if binaries_root.get("workflow").is_some() {
+                return Err(anyhow!("binaries: `workflow` needs `source actions`"));
+            }
+            Ok(None)
+        }
+        "actions" => {
+            let branch = match binaries_root.get("branch") {
+                Some(_) => string(binaries_root, "branch").context("binaries")?,
+                None => "main",
+            };
+            Ok(Some(ActionsYini {
+                workflow: string(binaries_root, "workflow")
+                    .context("binaries")?
+                    .to_string(),
+                branch: branch.to_string(),
+            }))
+        }
+        _ => Err(anyhow!(
+            "binaries.source: expected `release` or `actions`, got '{source}'"
+        )),
+    }
+}
+
+/// The release (default) or, with `actions`, the workflow `artifact`, `url` with optional
+/// `headers [ "Name" "ENV_VAR", ... ]`, or `path` (relative to the yini file).
+fn binaries_source(
+    platform_root: &yini::Object,
+    yini_path: &Path,
+    actions: bool,
+) -> anyhow::Result<BinariesSource> {
+    let headers = match platform_root.get("headers") {
+        Some(headers) => headers
+            .as_array()
+            .ok_or_else(|| anyhow!("headers: expected a list of \"Name\" \"ENV_VAR\" pairs"))?
+            .iter()
+            .map(env_header)
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    let artifact = platform_root.get("artifact").is_some();
+    if artifact && !actions {
+        return Err(anyhow!(
+            "`artifact` needs `source actions` in the binaries block"
+        ));
     }
+    match (platform_root.get("url"), platform_root.get("path")) {
+        (Some(_), Some(_)) => Err(anyhow!("set either `url` or `path`, not both")),
+        (Some(_), _) | (_, Some(_)) if artifact => {
+            Err(anyhow!("set only one of `artifact`, `url` and `path`"))
+        }
+        (Some(_), None) => {
+            let url = string(platform_root, "url")?;
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err(anyhow!("url: expected http:// or https://, got '{url}'"));
+            }
+            Ok(BinariesSource::Url {
+                url: url.to_string(),
+                headers,
+            })
+        }
+        (None, _) if !headers.is_empty() => Err(anyhow!("`headers` needs a `url`")),
+        (None, Some(_)) => {
+            let path = string(platform_root, "path")?;
+            let yini_dir = yini_path.parent().unwrap_or(Path::new(""));
+            Ok(BinariesSource::Path(yini_dir.join(path)))
+        }
+        (None, None) if actions => Ok(BinariesSource::Artifact(
+            string(platform_root, "artifact")?.to_string(),
+        )),
+        (None, None) => Ok(BinariesSource::Release),
+    }
+}
+
+fn env_header(value: &yini::Value) -> anyhow::Result<EnvHeader> {
+    let pair = value
+        .as_tuple()
+        .and_then(|(name, env)| Some((name.as_str()?, env.as_str()?)));
+    let Some((name, env)) = pair else {
+        return Err(anyhow!("headers: expected \"Name\" \"ENV_VAR\" pairs"));
+    };
+    Ok(EnvHeader {
+        name: name.to_string(),
+        env: env.to_string(),
+    })
+}
+
+/// `git@host:org/name.git`, a `:` before the first `/`.
+fn is_scp_like_url(repo: &str) -> bool {
+    repo.find(':')
+        .is_some_and(|colon| !repo[..colon].contains('/'))
+}
+
+/// GitLab projects can be in nested groups, `group/subgroup/name`.
+fn gitlab_project(binaries_root: &yini::Object) -> anyhow::Result<GitHubShortName> {
+    let repo = string(binaries_root, "repo").context("binaries.repo")?;
+    match repo.rsplit_once('/') {
+        Some((group, name)) if !group.is_empty() && !name.is_empty() => Ok(GitHubShortName {
+            org: group.to_string(),
+            name: name.to_string(),
+        }),
+        _ => Err(anyhow!(
+            "binaries.repo: expected `group/name`, got '{repo}'"
+        )),
+    }
+}
+
+fn size_budget(parent: &yini::Object) -> anyhow::Result<SizeBudget> {
+    Ok(SizeBudget {
+        max_size: size(parent, "max_size")?,
+        max_file_size: size(parent, "max_file_size")?,
+    })
+}
+
+/// Optional size, either bytes (`max_size 1000000`) or with a unit (`max_size "2GB"`).
+/// `KB`, `MB`, `GB` and `TB` are powers of 1000, `KiB`, `MiB`, `GiB` and `TiB` of 1024.
+fn size(parent: &yini::Object, key: &str) -> anyhow::Result<Option<u64>> {
+    let Some(value) = parent.get(key) else {
+        return Ok(None);
+    };
+    if let Some(bytes) = value.as_int() {
+        return u64::try_from(bytes)
+            .map(Some)
+            .map_err(|_| anyhow!("`{key}` must not be negative"));
+    }
+    let text = value
+        .as_str()
+        .ok_or_else(|| anyhow!("`{key}` must be a size like 500MB"))?;
+    parse_size(text)
+        .map(Some)
+        .ok_or_else(|| anyhow!("`{key}`: can not read size '{text}', expected e.g. 500MB or 2GiB"))
+}
+
+fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" | "k" => 1_000,
+        "mb" | "m" => 1_000_000,
+        "gb" | "g" => 1_000_000_000,
+        "tb" | "t" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Optional `key [ "a", "b" ]`, empty if the key is missing.
+fn string_array(object: &yini::Object, key: &str) -> Vec<String> {
+    object
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(|v| v.as_str())
+                .map(|s| s.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Consistency problems that parsing alone does not catch.
+pub fn validate_yini(ini: &BoilerYini) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if ini.app_id == 0 {
+        problems.push("steam_app_id must not be 0".to_string());
+    }
+
+    let depots = [
+        ("content", ini.content.depot),
+        ("binaries.macos", ini.binaries.macos.depot),
+        ("binaries.linux", ini.binaries.linux.depot),
+        ("binaries.windows", ini.binaries.windows.depot),
+    ];
+    for (index, (name, depot)) in depots.iter().enumerate() {
+        if *depot == 0 {
+            problems.push(format!("{name}.depot must not be 0"));
+        }
+        if *depot == ini.app_id {
+            problems.push(format!("{name}.depot {depot} is the app id"));
+        }
+        if let Some((other, _)) = depots[..index].iter().find(|(_, other)| other == depot) {
+            problems.push(format!("{name}.depot {depot} is also used by {other}"));
+        }
+    }
+
+    if ini.binaries.uses_release() && ini.binaries.version.is_empty() {
+        problems.push("binaries.version must not be empty".to_string());
+    }
+
+    if let ContentSource::Path(dir) = &ini.content.source
+        && !dir.is_dir()
+    {
+        problems.push(format!("content.path {dir:?} is not a directory"));
+    }
+
+    if ini.content.copy.is_empty() {
+        problems.push("content.copy is empty, nothing would be staged".to_string());
+    }
+
+    if !ini.allowed_branches.is_empty() {
+        for protected in &ini.protected_branches {
+            if !ini
+                .allowed_branches
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(protected))
+            {
+                problems.push(format!(
+                    "protected branch '{protected}' is not in allowed_branches"
+                ));
+            }
+        }
+    }
+
+    problems
+}
 
-    Some(ini)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal yini, with `root`, `binaries`, `linux` and `content` added to the root, the
+    /// binaries block, its linux block and the content block.
+    fn parse(root: &str, binaries: &str, linux: &str, content: &str) -> anyhow::Result<BoilerYini> {
+        parse_platforms(root, binaries, ["", "", linux], content)
+    }
+
+    /// Like `parse`, with `platforms` added to the macos, windows and linux blocks.
+    fn parse_platforms(
+        root: &str,
+        binaries: &str,
+        platforms: [&str; 3],
+        content: &str,
+    ) -> anyhow::Result<BoilerYini> {
+        let [macos, windows, linux] = platforms;
+        let text = format!(
+            r#"steam_app_id 1234560
+{root}
+binaries {{
+    repo "org/eng"
+    name eng
+    version "0.1.2"
+{binaries}
+    macos {{
+        depot 1234562
+{macos}
+    }}
+    windows {{
+        depot 1234563
+{windows}
+    }}
+    linux {{
+        depot 1234564
+{linux}
+    }}
+}}
+content {{
+    depot 1234561
+    path "/tmp/content"
+    copy [ "assets" "data/assets" ]
+{content}
+}}
+"#
+        );
+        parse_yini_str(&text, Path::new("game/a.yini"))
+    }
+
+    fn parse_content(content: &str) -> anyhow::Result<BoilerYini> {
+        parse("", "", "", content)
+    }
+
+    #[test]
+    fn buildinfo_format_defaults_to_txt() {
+        let ini = parse("", "", "", "").unwrap();
+        assert_eq!(ini.buildinfo_format, BuildInfoFormat::Txt);
+    }
+
+    #[test]
+    fn buildinfo_format_txt() {
+        let ini = parse(r#"buildinfo_format "txt""#, "", "", "").unwrap();
+        assert_eq!(ini.buildinfo_format, BuildInfoFormat::Txt);
+    }
+
+    #[test]
+    fn buildinfo_format_json() {
+        let ini = parse("buildinfo_format json", "", "", "").unwrap();
+        assert_eq!(ini.buildinfo_format, BuildInfoFormat::Json);
+    }
+
+    #[test]
+    fn buildinfo_format_both() {
+        let ini = parse("buildinfo_format both", "", "", "").unwrap();
+        assert_eq!(ini.buildinfo_format, BuildInfoFormat::Both);
+    }
+
+    #[test]
+    fn unquoted_txt_asks_for_quotes() {
+        let err = parse("buildinfo_format txt", "", "", "")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("game/a.yini:2:18: InvalidBooleanLiteral"),
+            "{err}"
+        );
+        assert!(err.contains(r#""txt""#), "{err}");
+    }
+
+    #[test]
+    fn lfs_defaults_to_all() {
+        let ini = parse_content("").unwrap();
+        assert_eq!(ini.content.lfs, LfsMode::All);
+        assert!(matches!(ini.content.lfs_fetch(), LfsFetch::All));
+    }
+
+    #[test]
+    fn lfs_copied_pulls_the_copy_sources() {
+        let ini = parse_content("    lfs copied").unwrap();
+        assert_eq!(ini.content.lfs, LfsMode::Copied);
+        let LfsFetch::Paths(paths) = ini.content.lfs_fetch() else {
+            panic!("expected paths");
+        };
+        assert_eq!(paths, ["assets", "assets/**"]);
+    }
+
+    #[test]
+    fn lfs_off() {
+        let ini = parse_content("    lfs off").unwrap();
+        assert!(matches!(ini.content.lfs_fetch(), LfsFetch::Off));
+    }
+
+    #[test]
+    fn lfs_rejects_unknown_modes() {
+        let err = parse_content("    lfs some").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("expected `all`, `copied` or `off`, got 'some'"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn submodules_default_to_none() {
+        let ini = parse_content("").unwrap();
+        assert_eq!(ini.content.submodules, Submodules::None);
+    }
+
+    #[test]
+    fn submodules_shallow_and_recursive() {
+        let ini = parse_content("    submodules shallow").unwrap();
+        assert_eq!(ini.content.submodules, Submodules::Shallow);
+        assert_eq!(ini.content.clone_options().submodules, Submodules::Shallow);
+        let ini = parse_content("    submodules recursive").unwrap();
+        assert_eq!(ini.content.submodules, Submodules::Recursive);
+    }
+
+    #[test]
+    fn submodules_reject_unknown_values() {
+        let err = parse_content("    submodules all").unwrap_err();
+        assert!(
+            format!("{err:#}").starts_with("content.submodules: expected `none`"),
+            "{err:#}"
+        );
+    }
+
+    fn parse_linux(linux: &str) -> anyhow::Result<BoilerYini> {
+        parse("", "", linux, "")
+    }
+
+    fn linux_error(linux: &str) -> String {
+        format!("{:#}", parse_linux(linux).unwrap_err())
+    }
+
+    #[test]
+    fn binaries_default_to_the_release() {
+        let ini = parse_linux("").unwrap();
+        assert!(matches!(ini.binaries.linux.source, BinariesSource::Release));
+        assert!(ini.binaries.uses_release());
+    }
+
+    #[test]
+    fn binaries_url_with_headers() {
+        let ini = parse_linux(
+            r#"        url "https://example.com/eng-linux.tar.gz"
+        headers [
+            "X-Api-Key" "PARTNER_KEY",
+            "X-Team" "TEAM_ID",
+        ]"#,
+        )
+        .unwrap();
+        let BinariesSource::Url { url, headers } = &ini.binaries.linux.source else {
+            panic!("expected a url, got {:?}", ini.binaries.linux.source);
+        };
+        assert_eq!(url, "https://example.com/eng-linux.tar.gz");
+        let headers: Vec<(&str, &str)> = headers
+            .iter()
+            .map(|header| (header.name.as_str(), header.env.as_str()))
+            .collect();
+        assert_eq!(
+            headers,
+            [("X-Api-Key", "PARTNER_KEY"), ("X-Team", "TEAM_ID")]
+        );
+        // the other platforms still need the release
+        assert!(ini.binaries.uses_release());
+    }
+
+    #[test]
+    fn binaries_path_is_relative_to_the_yini() {
+        let ini = parse_linux(r#"        path "dist/linux""#).unwrap();
+        let BinariesSource::Path(path) = &ini.binaries.linux.source else {
+            panic!("expected a path, got {:?}", ini.binaries.linux.source);
+        };
+        assert_eq!(path, Path::new("game/dist/linux"));
+    }
+
+    #[test]
+    fn binaries_url_must_be_http() {
+        assert!(
+            linux_error(r#"        url "ftp://example.com/eng.zip""#)
+                .contains("url: expected http:// or https://"),
+        );
+    }
+
+    #[test]
+    fn binaries_url_or_path() {
+        assert!(
+            linux_error(
+                r#"        url "https://example.com/eng.zip"
+        path "dist/linux""#
+            )
+            .starts_with("binaries.linux: set either `url` or `path`")
+        );
+    }
+
+    #[test]
+    fn binaries_headers_need_a_url() {
+        let err = linux_error(
+            r#"        path "dist/linux"
+        headers [ "X-Api-Key" "PARTNER_KEY" ]"#,
+        );
+        assert!(err.contains("`headers` needs a `url`"), "{err}");
+    }
+
+    #[test]
+    fn binaries_headers_are_pairs() {
+        let err = linux_error(
+            r#"        url "https://example.com/eng.zip"
+        headers [ "X-Api-Key" ]"#,
+        );
+        assert!(err.contains("headers: expected"), "{err}");
+    }
+
+    /// A yini with `source actions` and `binaries` added to the binaries block.
+    fn parse_actions(binaries: &str) -> anyhow::Result<BoilerYini> {
+        parse_platforms(
+            "",
+            &format!("    source actions\n{binaries}"),
+            [
+                r#"        artifact "eng-macos""#,
+                r#"        artifact "eng-windows""#,
+                r#"        path "dist/linux""#,
+            ],
+            "",
+        )
+    }
+
+    #[test]
+    fn source_actions_downloads_artifacts() {
+        let ini = parse_actions(r#"    workflow "nightly.yml""#).unwrap();
+        let actions = ini.binaries.actions.as_ref().unwrap();
+        assert_eq!(actions.workflow, "nightly.yml");
+        assert_eq!(actions.branch, "main");
+        assert!(
+            matches!(&ini.binaries.macos.source, BinariesSource::Artifact(name) if name == "eng-macos")
+        );
+        assert!(matches!(ini.binaries.linux.source, BinariesSource::Path(_)));
+        assert!(ini.binaries.uses_actions());
+        assert!(!ini.binaries.uses_release());
+    }
+
+    #[test]
+    fn source_actions_branch() {
+        let ini = parse_actions(
+            r#"    workflow "nightly.yml"
+    branch "develop""#,
+        )
+        .unwrap();
+        assert_eq!(ini.binaries.actions.unwrap().branch, "develop");
+    }
+
+    #[test]
+    fn source_actions_needs_a_workflow() {
+        let err = format!("{:#}", parse_actions("").unwrap_err());
+        assert!(err.starts_with("binaries: "), "{err}");
+        assert!(err.contains("workflow"), "{err}");
+    }
+
+    #[test]
+    fn source_rejects_unknown_values() {
+        let err = format!("{:#}", parse("", "    source nightly", "", "").unwrap_err());
+        assert!(
+            err.contains("binaries.source: expected `release` or `actions`, got 'nightly'"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn artifact_needs_source_actions() {
+        let err = linux_error(r#"        artifact "eng-linux""#);
+        assert!(err.contains("`artifact` needs `source actions`"), "{err}");
+    }
+
+    #[test]
+    fn workflow_needs_source_actions() {
+        let err = format!(
+            "{:#}",
+            parse("", r#"    workflow "nightly.yml""#, "", "").unwrap_err()
+        );
+        assert!(err.contains("`workflow` needs `source actions`"), "{err}");
+    }
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("1234"), Some(1234));
+        assert_eq!(parse_size("500B"), Some(500));
+        assert_eq!(parse_size("2KB"), Some(2_000));
+        assert_eq!(parse_size("500MB"), Some(500_000_000));
+        assert_eq!(parse_size("2GB"), Some(2_000_000_000));
+        assert_eq!(parse_size("1TB"), Some(1_000_000_000_000));
+        assert_eq!(parse_size("2KiB"), Some(2_048));
+        assert_eq!(parse_size("3MiB"), Some(3 << 20));
+        assert_eq!(parse_size("2GiB"), Some(2 << 30));
+        assert_eq!(parse_size("1TiB"), Some(1 << 40));
+        assert_eq!(parse_size("4g"), Some(4_000_000_000));
+        assert_eq!(parse_size("1.5GiB"), Some(3 << 29));
+    }
+
+    #[test]
+    fn parse_size_ignores_case_and_whitespace() {
+        for text in ["2GiB", "2gib", "2GIB", "2 GiB", " 2GiB ", "\t2  gib\n"] {
+            assert_eq!(parse_size(text), Some(2 << 30), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn parse_size_rejects_invalid_input() {
+        for text in [
+            "", "GB", "-1GB", "2 XB", "2GBs", "1.5.2GB", "2 G B", "two GB", ".",
+        ] {
+            assert_eq!(parse_size(text), None, "{text:?}");
+        }
+    }
 }